@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::RedditItem;
+
+/// Hosts whose query strings only sign or size the media, e.g. reddit signs
+/// preview urls per request. Elsewhere the query can be what identifies the
+/// media, as in `cdn.embedly.com/widgets/media.html?src=...`.
+const SIGNED_HOSTS: [&str; 5] = ["i.redd.it", "preview.redd.it", "external-preview.redd.it", "imgur.com", "i.imgur.com"];
+
+/// Strips what differs between copies of the same media: scheme, fragment,
+/// signature queries and the preview host.
+pub fn normalize_media_url(url: &str) -> String {
+  let url = url.split('#').next().unwrap_or(url);
+  let url = url.trim_start_matches("https://").trim_start_matches("http://");
+  let (host, rest) = match url.find(['/', '?']) {
+    Some(index) => (&url[..index], &url[index..]),
+    None => (url, ""),
+  };
+  let host = host.to_ascii_lowercase();
+  let host = match host.as_str() {
+    "www.imgur.com" | "m.imgur.com" => "imgur.com",
+    host => host,
+  };
+  let (path, query) = match rest.split_once('?') {
+    Some((path, query)) => (path, query),
+    None => (rest, ""),
+  };
+  let query = if query.is_empty() || SIGNED_HOSTS.contains(&host) { String::new() } else { format!("?{}", query) };
+  let host = if host == "preview.redd.it" { "i.redd.it" } else { host };
+  format!("{}{}{}", host, path.trim_end_matches('/'), query)
+}
+
+/// Remembers what was already loaded so later pages can't repeat it.
+#[derive(Default, Clone)]
+pub struct Deduplicator {
+  /// Post key -> (fullname, batch) of its first occurrence.
+  posts: HashMap<String, (String, usize)>,
+  media: HashSet<String>,
+  batch: usize,
+  by_crosspost: bool,
+}
+
+impl Deduplicator {
+  pub fn new(by_crosspost: bool) -> Self {
+    Self {
+      by_crosspost,
+      ..Self::default()
+    }
+  }
+
+  pub fn set_by_crosspost(&mut self, by_crosspost: bool) {
+    self.by_crosspost = by_crosspost;
+  }
+
+  pub fn clear(&mut self) {
+    self.posts.clear();
+    self.media.clear();
+  }
+
+  fn post_key<'a>(&self, item: &'a RedditItem) -> &'a str {
+    match &item.crosspost_parent {
+      Some(parent) if self.by_crosspost => parent,
+      _ => &item.name,
+    }
+  }
+
+  /// Splits one loaded page into items to keep and duplicates of something
+  /// loaded earlier. Items of the same post within the page (galleries) are
+  /// not duplicates of each other.
+  pub fn filter(&mut self, items: Vec<RedditItem>) -> (Vec<RedditItem>, Vec<RedditItem>) {
+    self.batch += 1;
+    let mut kept = Vec::new();
+    let mut skipped = Vec::new();
+    for item in items {
+      let key = self.post_key(&item).to_string();
+      let duplicate_post = !key.is_empty() && match self.posts.get(&key) {
+        Some((name, batch)) => *name != item.name || *batch != self.batch,
+        None => false,
+      };
+      let media = normalize_media_url(item.media_url());
+      if duplicate_post || self.media.contains(&media) {
+        skipped.push(item);
+        continue;
+      }
+      if !key.is_empty() {
+        self.posts.insert(key, (item.name.clone(), self.batch));
+      }
+      self.media.insert(media);
+      kept.push(item);
+    }
+    (kept, skipped)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_picture;
+
+  fn crosspost(name: &str, parent: &str, url: &str) -> RedditItem {
+    RedditItem { crosspost_parent: Some(parent.to_string()), ..test_picture(name, url) }
+  }
+
+  fn names(items: &[RedditItem]) -> Vec<&str> {
+    items.iter().map(|item| item.name.as_str()).collect()
+  }
+
+  #[test]
+  fn skips_posts_and_media_seen_on_earlier_pages() {
+    let mut dedup = Deduplicator::new(false);
+    let (kept, _) = dedup.filter(vec![test_picture("t3_a", "https://i.redd.it/a.jpg"), test_picture("t3_a", "https://i.redd.it/b.jpg")]);
+    assert_eq!(names(&kept), ["t3_a", "t3_a"]);
+    let (kept, skipped) = dedup.filter(vec![
+      test_picture("t3_a", "https://i.redd.it/c.jpg"),
+      test_picture("t3_b", "https://preview.redd.it/b.jpg?s=1"),
+      test_picture("t3_c", "https://i.redd.it/d.jpg"),
+    ]);
+    assert_eq!(names(&kept), ["t3_c"]);
+    assert_eq!(names(&skipped), ["t3_a", "t3_b"]);
+  }
+
+  #[test]
+  fn crossposts_count_as_their_parent_when_asked() {
+    let page = || vec![test_picture("t3_a", "https://i.redd.it/a.jpg"), crosspost("t3_b", "t3_a", "https://i.redd.it/b.jpg")];
+    let (kept, _) = Deduplicator::new(false).filter(page());
+    assert_eq!(names(&kept), ["t3_a", "t3_b"]);
+    let (kept, skipped) = Deduplicator::new(true).filter(page());
+    assert_eq!(names(&kept), ["t3_a"]);
+    assert_eq!(names(&skipped), ["t3_b"]);
+  }
+
+  #[test]
+  fn clear_forgets_everything() {
+    let mut dedup = Deduplicator::new(false);
+    dedup.filter(vec![test_picture("t3_a", "https://i.redd.it/a.jpg")]);
+    dedup.clear();
+    let (kept, _) = dedup.filter(vec![test_picture("t3_a", "https://i.redd.it/a.jpg")]);
+    assert_eq!(names(&kept), ["t3_a"]);
+  }
+
+  #[test]
+  fn strips_signature_queries() {
+    assert_eq!(normalize_media_url("https://preview.redd.it/abc.jpg?width=640&s=123"), "i.redd.it/abc.jpg");
+    assert_eq!(normalize_media_url("http://i.redd.it/abc.jpg"), "i.redd.it/abc.jpg");
+    assert_eq!(normalize_media_url("https://m.imgur.com/xyz/?foo=1#top"), "imgur.com/xyz");
+  }
+
+  #[test]
+  fn keeps_identifying_queries() {
+    let first = normalize_media_url("https://cdn.embedly.com/widgets/media.html?src=a&url=b");
+    let second = normalize_media_url("https://cdn.embedly.com/widgets/media.html?src=c&url=d");
+    assert_ne!(first, second);
+    assert_eq!(normalize_media_url("https://clips.twitch.tv/embed?clip=X"), "clips.twitch.tv/embed?clip=X");
+  }
+}
@@ -0,0 +1,441 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{RedditItem, RedditItemType, RedditListingItemData};
+
+/// Pictures closer to square than this, relative to their aspect ratio, count as square.
+const SQUARE_TOLERANCE: f64 = 0.1;
+/// How far a picture's aspect ratio may be from the screen's to match it.
+const SCREEN_TOLERANCE: f64 = 0.15;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+  #[default]
+  Any,
+  Landscape,
+  Portrait,
+  Square,
+}
+
+impl Orientation {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Orientation::Any => "any",
+      Orientation::Landscape => "landscape",
+      Orientation::Portrait => "portrait",
+      Orientation::Square => "square",
+    }
+  }
+
+  pub fn parse(value: &str) -> Option<Self> {
+    match value {
+      "any" => Some(Orientation::Any),
+      "landscape" => Some(Orientation::Landscape),
+      "portrait" => Some(Orientation::Portrait),
+      "square" => Some(Orientation::Square),
+      _ => None,
+    }
+  }
+
+  fn of(aspect: f64) -> Self {
+    if (aspect - 1.0).abs() <= SQUARE_TOLERANCE {
+      Orientation::Square
+    } else if aspect > 1.0 {
+      Orientation::Landscape
+    } else {
+      Orientation::Portrait
+    }
+  }
+}
+
+/// Which kinds of posts, and from which hosts, make it into the slideshow.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContentFilter {
+  pub pictures: bool,
+  pub videos: bool,
+  pub embeds: bool,
+  pub galleries: bool,
+  /// When not empty, only media from these domains (or their subdomains).
+  #[serde(default)]
+  pub allow_domains: Vec<String>,
+  #[serde(default)]
+  pub deny_domains: Vec<String>,
+  /// Size limits for pictures; pictures of unknown size always pass.
+  #[serde(default)]
+  pub min_width: i32,
+  #[serde(default)]
+  pub min_height: i32,
+  #[serde(default)]
+  pub min_megapixels: f64,
+  #[serde(default)]
+  pub orientation: Orientation,
+  /// Only pictures shaped about like the screen.
+  #[serde(default)]
+  pub match_screen: bool,
+}
+
+impl Default for ContentFilter {
+  fn default() -> Self {
+    Self {
+      pictures: true,
+      videos: true,
+      embeds: true,
+      galleries: true,
+      allow_domains: Vec::new(),
+      deny_domains: Vec::new(),
+      min_width: 0,
+      min_height: 0,
+      min_megapixels: 0.0,
+      orientation: Orientation::Any,
+      match_screen: false,
+    }
+  }
+}
+
+/// Host of `url`, lowercased and without a port.
+fn host(url: &str) -> String {
+  let url = url.split("://").nth(1).unwrap_or(url);
+  let host = url.split(['/', '?', '#']).next().unwrap_or(url);
+  let host = host.rsplit('@').next().unwrap_or(host);
+  host.split(':').next().unwrap_or(host).to_ascii_lowercase()
+}
+
+fn on_domain(host: &str, domain: &str) -> bool {
+  host == domain || host.strip_suffix(domain).is_some_and(|rest| rest.ends_with('.'))
+}
+
+/// Domains out of free text like `i.redd.it, imgur.com`.
+pub fn parse_domains(value: &str) -> Vec<String> {
+  value.split(|c: char| c == ',' || c.is_whitespace())
+      .map(|domain| domain.trim_start_matches("*.").trim_matches('.').to_ascii_lowercase())
+      .filter(|domain| !domain.is_empty())
+      .collect()
+}
+
+impl ContentFilter {
+  /// Whether `item` passes, `screen_aspect` being the screen's width over height.
+  pub fn matches(&self, item: &RedditItem, screen_aspect: f64) -> bool {
+    let kind = match item.item {
+      RedditItemType::Picture { .. } => self.pictures,
+      RedditItemType::Video { .. } => self.videos,
+      RedditItemType::Embed { .. } => self.embeds,
+    };
+    if !kind || (item.gallery && !self.galleries) {
+      return false;
+    }
+    if let RedditItemType::Picture { width, height, .. } = item.item {
+      if !self.fits(width, height, screen_aspect) {
+        return false;
+      }
+    }
+
+    let host = host(item.media_url());
+    if !self.allow_domains.is_empty() && !self.allow_domains.iter().any(|domain| on_domain(&host, domain)) {
+      return false;
+    }
+    !self.deny_domains.iter().any(|domain| on_domain(&host, domain))
+  }
+
+  fn fits(&self, width: i32, height: i32, screen_aspect: f64) -> bool {
+    if width <= 0 || height <= 0 {
+      return true;
+    }
+    if width < self.min_width || height < self.min_height {
+      return false;
+    }
+    if (width as f64 * height as f64) / 1_000_000.0 < self.min_megapixels {
+      return false;
+    }
+    let aspect = width as f64 / height as f64;
+    if self.orientation != Orientation::Any && Orientation::of(aspect) != self.orientation {
+      return false;
+    }
+    !self.match_screen || screen_aspect <= 0.0 || (aspect - screen_aspect).abs() / screen_aspect <= SCREEN_TOLERANCE
+  }
+}
+
+/// Part of a post a `KeywordRule` looks at.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleField {
+  Title,
+  Author,
+  Flair,
+  Domain,
+}
+
+impl RuleField {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      RuleField::Title => "title",
+      RuleField::Author => "author",
+      RuleField::Flair => "flair",
+      RuleField::Domain => "domain",
+    }
+  }
+
+  pub fn parse(value: &str) -> Option<Self> {
+    match value {
+      "title" => Some(RuleField::Title),
+      "author" => Some(RuleField::Author),
+      "flair" => Some(RuleField::Flair),
+      "domain" => Some(RuleField::Domain),
+      _ => None,
+    }
+  }
+
+  fn of(self, item: &RedditItem) -> &str {
+    match self {
+      RuleField::Title => &item.title,
+      RuleField::Author => &item.author,
+      RuleField::Flair => &item.flair,
+      RuleField::Domain => &item.domain,
+    }
+  }
+}
+
+/// Shows or hides posts whose `field` contains `pattern`, ignoring case.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct KeywordRule {
+  pub field: RuleField,
+  pub pattern: String,
+  /// `pattern` is a regular expression rather than plain text.
+  #[serde(default)]
+  pub regex: bool,
+  /// Hide matching posts; otherwise only matching posts are shown.
+  #[serde(default)]
+  pub exclude: bool,
+}
+
+/// Compiled `KeywordRule`s. A post passes when it matches no exclude rule
+/// and, if there are include rules, at least one of them.
+#[derive(Debug, Clone, Default)]
+pub struct KeywordFilter {
+  rules: Vec<KeywordRule>,
+  /// One per rule, `None` for rules that failed to compile.
+  compiled: Vec<Option<Regex>>,
+}
+
+impl KeywordFilter {
+  pub fn new(rules: Vec<KeywordRule>) -> Self {
+    let compiled = rules.iter()
+        .map(|rule| {
+          let pattern = if rule.regex { rule.pattern.clone() } else { regex::escape(&rule.pattern) };
+          RegexBuilder::new(&pattern).case_insensitive(true).build().ok()
+        })
+        .collect();
+    Self { rules, compiled }
+  }
+
+  pub fn rules(&self) -> &[KeywordRule] {
+    &self.rules
+  }
+
+  /// Whether the rule at `index` is an invalid regex, and so ignored.
+  pub fn is_invalid(&self, index: usize) -> bool {
+    matches!(self.compiled.get(index), Some(None))
+  }
+
+  pub fn matches(&self, item: &RedditItem) -> bool {
+    let mut included = None;
+    for (rule, regex) in self.rules.iter().zip(&self.compiled) {
+      let regex = match regex {
+        Some(regex) => regex,
+        None => continue,
+      };
+      let found = regex.is_match(rule.field.of(item));
+      if rule.exclude {
+        if found {
+          return false;
+        }
+      } else {
+        included = Some(included.unwrap_or(false) || found);
+      }
+    }
+    included.unwrap_or(true)
+  }
+}
+
+/// Limits on a post's score, comments and age, checked while converting
+/// listings so they work on any sort.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct PostThresholds {
+  #[serde(default)]
+  pub min_score: Option<i64>,
+  #[serde(default)]
+  pub min_comments: Option<i64>,
+  /// Age limits in hours.
+  #[serde(default)]
+  pub max_age_hours: Option<f64>,
+  #[serde(default)]
+  pub min_age_hours: Option<f64>,
+}
+
+const MIN_SCORE: &str = "min_score";
+const MIN_COMMENTS: &str = "min_comments";
+const MAX_AGE_HOURS: &str = "max_age_hours";
+const MIN_AGE_HOURS: &str = "min_age_hours";
+
+impl PostThresholds {
+  pub fn passes(&self, post: &RedditListingItemData, now: f64) -> bool {
+    let age_hours = (now - post.created_utc) / 3600.0;
+    self.min_score.is_none_or(|min| post.score >= min)
+        && self.min_comments.is_none_or(|min| post.num_comments >= min)
+        && self.max_age_hours.is_none_or(|max| age_hours <= max)
+        && self.min_age_hours.is_none_or(|min| age_hours >= min)
+  }
+
+  /// Query parameters handing the thresholds to the backend, each followed by `&`.
+  pub fn to_query(&self) -> String {
+    let mut query = String::new();
+    if let Some(min) = self.min_score {
+      query.push_str(&format!("{}={}&", MIN_SCORE, min));
+    }
+    if let Some(min) = self.min_comments {
+      query.push_str(&format!("{}={}&", MIN_COMMENTS, min));
+    }
+    if let Some(max) = self.max_age_hours {
+      query.push_str(&format!("{}={}&", MAX_AGE_HOURS, max));
+    }
+    if let Some(min) = self.min_age_hours {
+      query.push_str(&format!("{}={}&", MIN_AGE_HOURS, min));
+    }
+    query
+  }
+
+  /// Takes the thresholds out of a query, returning them and the rest of it.
+  pub fn from_query(query: &str) -> (Self, String) {
+    let mut thresholds = Self::default();
+    let mut rest = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+      let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+      match key {
+        MIN_SCORE => thresholds.min_score = value.parse().ok(),
+        MIN_COMMENTS => thresholds.min_comments = value.parse().ok(),
+        MAX_AGE_HOURS => thresholds.max_age_hours = value.parse().ok(),
+        MIN_AGE_HOURS => thresholds.min_age_hours = value.parse().ok(),
+        _ => rest.push(pair),
+      }
+    }
+    (thresholds, rest.join("&"))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_picture;
+
+  fn sized(width: i32, height: i32) -> RedditItem {
+    let url = "https://i.redd.it/a.jpg".to_string();
+    RedditItem { item: RedditItemType::Picture { source_set: String::new(), url, width, height }, ..test_picture("t3_a", "") }
+  }
+
+  #[test]
+  fn filters_kinds() {
+    let picture = test_picture("t3_a", "https://i.redd.it/a.jpg");
+    let video = RedditItem {
+      item: RedditItemType::Video { mime: "video/mp4".to_string(), url: "https://v.redd.it/a.mp4".to_string(), fallbacks: Vec::new() },
+      ..picture.clone()
+    };
+    let gallery = RedditItem { gallery: true, ..picture.clone() };
+    let no_videos = ContentFilter { videos: false, ..ContentFilter::default() };
+    assert!(no_videos.matches(&picture, 0.0));
+    assert!(!no_videos.matches(&video, 0.0));
+    let no_galleries = ContentFilter { galleries: false, ..ContentFilter::default() };
+    assert!(no_galleries.matches(&picture, 0.0));
+    assert!(!no_galleries.matches(&gallery, 0.0));
+  }
+
+  #[test]
+  fn filters_domains() {
+    let filter = ContentFilter {
+      allow_domains: parse_domains("*.redd.it, imgur.com"),
+      deny_domains: vec!["preview.redd.it".to_string()],
+      ..ContentFilter::default()
+    };
+    assert!(filter.matches(&test_picture("t3_a", "https://i.redd.it/a.jpg"), 0.0));
+    assert!(filter.matches(&test_picture("t3_a", "https://user@i.imgur.com:443/a.jpg"), 0.0));
+    assert!(!filter.matches(&test_picture("t3_a", "https://preview.redd.it/a.jpg"), 0.0));
+    assert!(!filter.matches(&test_picture("t3_a", "https://notimgur.com/a.jpg"), 0.0));
+  }
+
+  #[test]
+  fn filters_picture_sizes() {
+    let filter = ContentFilter { min_width: 1000, min_megapixels: 1.0, ..ContentFilter::default() };
+    assert!(filter.matches(&sized(1920, 1080), 0.0));
+    assert!(!filter.matches(&sized(800, 1080), 0.0));
+    assert!(!filter.matches(&sized(1000, 900), 0.0));
+    // Unknown sizes always pass.
+    assert!(filter.matches(&sized(0, 0), 0.0));
+  }
+
+  #[test]
+  fn filters_orientation_and_screen_shape() {
+    let portrait = ContentFilter { orientation: Orientation::Portrait, ..ContentFilter::default() };
+    assert!(portrait.matches(&sized(1080, 1920), 0.0));
+    assert!(!portrait.matches(&sized(1920, 1080), 0.0));
+    let square = ContentFilter { orientation: Orientation::Square, ..ContentFilter::default() };
+    assert!(square.matches(&sized(1000, 1050), 0.0));
+
+    let screen = ContentFilter { match_screen: true, ..ContentFilter::default() };
+    assert!(screen.matches(&sized(1920, 1080), 16.0 / 9.0));
+    assert!(!screen.matches(&sized(1080, 1920), 16.0 / 9.0));
+    // The screen's shape isn't known.
+    assert!(screen.matches(&sized(1080, 1920), 0.0));
+  }
+
+  fn rule(field: RuleField, pattern: &str, regex: bool, exclude: bool) -> KeywordRule {
+    KeywordRule { field, pattern: pattern.to_string(), regex, exclude }
+  }
+
+  #[test]
+  fn exclude_rules_win_over_include_rules() {
+    let item = RedditItem {
+      title: "Cat on a wall".to_string(),
+      author: "alice".to_string(),
+      domain: "i.redd.it".to_string(),
+      ..test_picture("t3_a", "https://i.redd.it/a.jpg")
+    };
+    let include = rule(RuleField::Title, "CAT", false, false);
+    assert!(KeywordFilter::new(vec![include.clone()]).matches(&item));
+    assert!(!KeywordFilter::new(vec![include.clone(), rule(RuleField::Author, "^ali", true, true)]).matches(&item));
+    assert!(KeywordFilter::new(vec![include, rule(RuleField::Author, "bob", false, true)]).matches(&item));
+    assert!(!KeywordFilter::new(vec![rule(RuleField::Title, "dog", false, false)]).matches(&item));
+    // One matching include rule is enough.
+    let either = vec![rule(RuleField::Title, "dog", false, false), rule(RuleField::Domain, "redd.it", false, false)];
+    assert!(KeywordFilter::new(either).matches(&item));
+  }
+
+  #[test]
+  fn invalid_regex_rules_are_ignored() {
+    let filter = KeywordFilter::new(vec![rule(RuleField::Title, "(", true, false), rule(RuleField::Title, "(", false, true)]);
+    assert!(filter.is_invalid(0));
+    assert!(!filter.is_invalid(1));
+    assert!(!filter.is_invalid(2));
+    assert!(filter.matches(&test_picture("t3_a", "https://i.redd.it/a.jpg")));
+  }
+
+  #[test]
+  fn thresholds_round_trip_through_query() {
+    let thresholds = PostThresholds { min_score: Some(10), min_comments: Some(2), max_age_hours: Some(1.5), min_age_hours: None };
+    let query = format!("t=week&{}after=t3_x", thresholds.to_query());
+    assert_eq!(PostThresholds::from_query(&query), (thresholds, "t=week&after=t3_x".to_string()));
+    assert_eq!(PostThresholds::from_query(""), (PostThresholds::default(), String::new()));
+  }
+
+  #[test]
+  fn thresholds_check_score_comments_and_age() {
+    let post: RedditListingItemData = serde_json::from_str(
+      r#"{"title": "t", "permalink": "/r/a/comments/x", "name": "t3_x", "url": "u", "score": 10, "num_comments": 3, "created_utc": 0}"#,
+    ).unwrap();
+    let hours = |hours: f64| hours * 3600.0;
+    assert!(PostThresholds { min_score: Some(10), min_comments: Some(3), ..PostThresholds::default() }.passes(&post, 0.0));
+    assert!(!PostThresholds { min_score: Some(11), ..PostThresholds::default() }.passes(&post, 0.0));
+    assert!(!PostThresholds { min_comments: Some(4), ..PostThresholds::default() }.passes(&post, 0.0));
+    assert!(PostThresholds { max_age_hours: Some(2.0), ..PostThresholds::default() }.passes(&post, hours(2.0)));
+    assert!(!PostThresholds { max_age_hours: Some(2.0), ..PostThresholds::default() }.passes(&post, hours(3.0)));
+    assert!(!PostThresholds { min_age_hours: Some(2.0), ..PostThresholds::default() }.passes(&post, hours(1.0)));
+  }
+}
@@ -0,0 +1,574 @@
+#[macro_use]
+extern crate lazy_static;
+
+mod dedup;
+mod filter;
+mod phash;
+mod playlist;
+mod shuffle;
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+pub use dedup::{normalize_media_url, Deduplicator};
+pub use filter::{parse_domains, ContentFilter, KeywordFilter, KeywordRule, Orientation, PostThresholds, RuleField};
+pub use phash::{dhash, hamming_distance, DHASH_HEIGHT, DHASH_WIDTH};
+pub use playlist::{Playlist, PlaylistError, PLAYLIST_VERSION};
+pub use shuffle::ShuffleOrder;
+
+fn is_zero(value: &i32) -> bool {
+  *value == 0
+}
+
+/// One way of playing a video; `image/*` sources are shown as still images.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VideoSource {
+  pub mime: String,
+  pub url: String,
+}
+
+impl VideoSource {
+  fn mp4(url: String) -> Self {
+    Self { mime: "video/mp4".to_string(), url }
+  }
+
+  fn gif(url: String) -> Self {
+    Self { mime: "image/gif".to_string(), url }
+  }
+
+  pub fn is_image(&self) -> bool {
+    self.mime.starts_with("image/")
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RedditItemType {
+  Picture {
+    source_set: String,
+    url: String,
+    /// Size of the full resolution source, 0 when not known.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    width: i32,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    height: i32,
+  },
+  Video {
+    mime: String,
+    url: String,
+    /// Sources to try, in order, when `url` fails to load.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    fallbacks: Vec<VideoSource>,
+  },
+  Embed {
+    url: String,
+    scrolling: String,
+    width: i32,
+    height: i32,
+  },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RedditItem {
+  pub title: String,
+  pub title_url: String,
+  /// Fullname of the post the item came from (e.g. `t3_abc123`).
+  #[serde(default)]
+  pub name: String,
+  /// Fullname of the original post when this one is a crosspost.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub crosspost_parent: Option<String>,
+  /// One of several pictures of a gallery post.
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub gallery: bool,
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub author: String,
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub flair: String,
+  /// Domain reddit files the post's link under, e.g. `i.redd.it`.
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub domain: String,
+  pub item: RedditItemType,
+}
+
+impl RedditItem {
+  pub fn media_url(&self) -> &str {
+    match &self.item {
+      RedditItemType::Picture { url, .. } => url,
+      RedditItemType::Video { url, .. } => url,
+      RedditItemType::Embed { url, .. } => url,
+    }
+  }
+
+  /// Candidate sources of a video, best first.
+  pub fn video_sources(&self) -> Vec<VideoSource> {
+    match &self.item {
+      RedditItemType::Video { mime, url, fallbacks } => {
+        let mut sources = vec![VideoSource { mime: mime.clone(), url: url.clone() }];
+        sources.extend(fallbacks.iter().filter(|source| source.url != *url).cloned());
+        sources
+      }
+      _ => Vec::new(),
+    }
+  }
+
+  /// Smallest rendition of a picture, good enough for hashing it.
+  pub fn thumbnail_url(&self) -> Option<&str> {
+    match &self.item {
+      RedditItemType::Picture { source_set, url, .. } => source_set.split(", ")
+          .next()
+          .and_then(|candidate| candidate.split(' ').next())
+          .filter(|candidate| !candidate.is_empty())
+          .or(Some(url)),
+      _ => None,
+    }
+  }
+}
+
+/// One page of already extracted items, as served by the backend.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RedditItemsPage {
+  pub items: Vec<RedditItem>,
+  pub after: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditListings {
+  pub data: Option<RedditListingsData>,
+}
+
+/// Body of `/api/subreddit_autocomplete.json`.
+#[derive(Deserialize, Debug)]
+pub struct SubredditAutocomplete {
+  pub subreddits: Vec<SubredditSuggestion>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SubredditSuggestion {
+  pub name: String,
+}
+
+/// Body of a listing url. `/random` redirects to a post, whose json is the
+/// post's own listing followed by its comments.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum RedditListingResponse {
+  Listing(RedditListings),
+  Post(Vec<RedditListings>),
+}
+
+impl RedditListingResponse {
+  pub fn get_items(self, thresholds: &PostThresholds, now: f64) -> Option<(Vec<RedditItem>, String)> {
+    match self {
+      RedditListingResponse::Listing(listings) => listings.get_items(thresholds, now),
+      RedditListingResponse::Post(listings) => listings.into_iter().next().and_then(|listings| listings.get_items(thresholds, now)),
+    }
+  }
+}
+
+fn extract_imgur_gifv_url(url: &str) -> Option<String> {
+  use regex::Regex;
+  lazy_static! {
+      static ref RE: Regex = Regex::new(r"/((\w+|\d+)).gifv").unwrap();
+  }
+
+  RE.captures(url)
+      .and_then(|x| x.get(1))
+      .map(|x| x.as_str().to_string())
+}
+
+fn extract_gfycat_gif_url(url: &str) -> Option<String> {
+  use regex::Regex;
+  lazy_static! {
+        static ref RE: Regex = Regex::new(r"^https?://thumbs\.gfycat\.com/(\w+|\d+)-(?:\w+|\d+|_)\.gif$").unwrap();
+  }
+  RE.captures(url)
+      .and_then(|x| x.get(1))
+      .map(|x| x.as_str().to_string())
+}
+
+fn extract_src_from_inframe_html(content: &str)  -> Option<String> {
+  use regex::Regex;
+  lazy_static! {
+        static ref RE: Regex = Regex::new("src=\"([^\"]*)\"").unwrap();
+  }
+  RE.captures(content)
+      .and_then(|x| x.get(1))
+      .map(|x| x.as_str().to_string())
+}
+
+
+impl RedditListings {
+  /// Items of the listing's posts that pass `thresholds`, `now` being the
+  /// current unix time in seconds, and the fullname to page in after, empty
+  /// when the listing has no more posts. `None` when there is no listing.
+  pub fn get_items(self, thresholds: &PostThresholds, now: f64) -> Option<(Vec<RedditItem>, String)> {
+    if let Some(data) = self.data {
+      let mut items = Vec::new();
+      let mut after = String::new();
+      for child in data.children {
+        if let Some(child_data) = child.data {
+          after = child_data.name.clone();
+          if child.kind != "t3" || !thresholds.passes(&child_data, now) {
+            continue;
+          }
+
+          if child_data.url.contains("imgur") && child_data.url.ends_with(".gifv") {
+            if let Some(url) = extract_imgur_gifv_url(&child_data.url) {
+              items.push(child_data.to_item(RedditItemType::Video {
+                mime: "video/mp4".to_string(),
+                url: format!("https://i.imgur.com/{}.mp4", url),
+                fallbacks: child_data.preview_fallbacks(),
+              }));
+              continue;
+            }
+          }
+
+          if let Some(RedditMedia { type_: Some(type_), oembed: Some(OEmbed { thumbnail_url: Some(thumbnail_url) }) }) = &child_data.media {
+            if type_ == "gfycat.com" {
+              if let Some(url) = extract_gfycat_gif_url(thumbnail_url) {
+                let mut fallbacks = child_data.preview_fallbacks();
+                fallbacks.push(VideoSource::gif(thumbnail_url.replace("&amp;", "&")));
+                items.push(child_data.to_item(RedditItemType::Video {
+                  mime: "video/mp4".to_string(),
+                  url: format!("https://giant.gfycat.com/{}.mp4", url),
+                  fallbacks,
+                }));
+                continue;
+              }
+            }
+          }
+
+          if let Some(RedditMediaEmbed { scrolling: Some(scrolling), width: Some(width), height: Some(height), content: Some(content) }) = &child_data.secure_media_embed {
+            if let Some(url) = extract_src_from_inframe_html(content) {
+              items.push(child_data.to_item(RedditItemType::Embed {
+                url: url.replace("&amp;", "&"),
+                scrolling: if *scrolling { "yes".to_string() } else { "no".to_string() },
+                width: *width,
+                height: *height,
+              }));
+
+              continue;
+            }
+          }
+
+          let gallery = child_data.gallery_items();
+          if !gallery.is_empty() {
+            for item in gallery {
+              let mut item = child_data.to_item(RedditItemType::Picture {
+                source_set: item.get_srcset(),
+                url: item.s.u.replace("&amp;", "&"),
+                width: item.s.x,
+                height: item.s.y,
+              });
+              item.gallery = true;
+              items.push(item);
+            }
+            continue;
+          }
+          if let Some(preview) = &child_data.preview {
+            if !preview.images.is_empty() {
+              for item in &preview.images {
+                if let Some(variants) = &item.variants {
+                  if let Some(mp4) = &variants.mp4 {
+                    items.push(child_data.to_item(RedditItemType::Video {
+                      mime: "video/mp4".to_string(),
+                      url: mp4.source.url.replace("&amp;", "&"),
+                      fallbacks: variants.gif.iter()
+                          .map(|gif| VideoSource::gif(gif.source.url.replace("&amp;", "&")))
+                          .collect(),
+                    }));
+                    continue;
+                  }
+
+                  if let Some(gif) = &variants.gif {
+                    items.push(child_data.to_item(RedditItemType::Picture {
+                      source_set: gif.get_srcset(),
+                      url: gif.source.url.replace("&amp;", "&"),
+                      width: gif.source.width,
+                      height: gif.source.height,
+                    }));
+                    continue;
+                  }
+                }
+
+                items.push(child_data.to_item(RedditItemType::Picture {
+                  source_set: item.get_srcset(),
+                  url: item.source.url.replace("&amp;", "&"),
+                  width: item.source.width,
+                  height: item.source.height,
+                }));
+              }
+              continue;
+            }
+          }
+        }
+      }
+      Some((items, after))
+    } else {
+      None
+    }
+  }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditListingsData {
+  pub children: Vec<RedditListingItem>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditListingItem {
+  pub kind: String,
+  pub data: Option<RedditListingItemData>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditListingItemData {
+  /// Pictures of a gallery by media id, see `gallery_items` for their order.
+  pub media_metadata: Option<BTreeMap<String, RedditGalleryItem>>,
+  pub gallery_data: Option<RedditGalleryData>,
+  pub preview: Option<RedditPreview>,
+  pub title: String,
+  pub permalink: String,
+  pub name: String,
+  pub url: String,
+  pub crosspost_parent: Option<String>,
+  pub secure_media_embed: Option<RedditMediaEmbed>,
+  pub media: Option<RedditMedia>,
+  #[serde(default)]
+  pub author: String,
+  pub link_flair_text: Option<String>,
+  #[serde(default)]
+  pub domain: String,
+  #[serde(default)]
+  pub score: i64,
+  #[serde(default)]
+  pub num_comments: i64,
+  /// Unix time in seconds.
+  #[serde(default)]
+  pub created_utc: f64,
+}
+
+impl RedditListingItemData {
+  /// Gallery pictures in the post's order, or by media id when reddit
+  /// doesn't give one.
+  fn gallery_items(&self) -> Vec<&RedditGalleryItem> {
+    let media_metadata = match &self.media_metadata {
+      Some(media_metadata) => media_metadata,
+      None => return Vec::new(),
+    };
+    match &self.gallery_data {
+      Some(gallery_data) => gallery_data.items.iter()
+          .filter_map(|item| media_metadata.get(&item.media_id))
+          .collect(),
+      None => media_metadata.values().collect(),
+    }
+  }
+
+  /// Reddit's own mp4 and gif renditions of the first preview image.
+  fn preview_fallbacks(&self) -> Vec<VideoSource> {
+    let mut fallbacks = Vec::new();
+    let variants = self.preview.as_ref()
+        .and_then(|preview| preview.images.first())
+        .and_then(|image| image.variants.as_ref());
+    if let Some(variants) = variants {
+      if let Some(mp4) = &variants.mp4 {
+        fallbacks.push(VideoSource::mp4(mp4.source.url.replace("&amp;", "&")));
+      }
+      if let Some(gif) = &variants.gif {
+        fallbacks.push(VideoSource::gif(gif.source.url.replace("&amp;", "&")));
+      }
+    }
+    fallbacks
+  }
+
+  fn to_item(&self, item: RedditItemType) -> RedditItem {
+    RedditItem {
+      title: self.title.clone(),
+      title_url: format!("https://www.reddit.com/{}", &self.permalink),
+      name: self.name.clone(),
+      crosspost_parent: self.crosspost_parent.clone(),
+      gallery: false,
+      author: self.author.clone(),
+      flair: self.link_flair_text.clone().unwrap_or_default(),
+      domain: self.domain.clone(),
+      item,
+    }
+  }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditMedia {
+  #[serde(rename = "type")]
+  pub type_: Option<String>,
+  pub oembed: Option<OEmbed>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct OEmbed {
+  pub thumbnail_url: Option<String>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditMediaEmbed {
+  pub scrolling: Option<bool>,
+  pub width: Option<i32>,
+  pub height: Option<i32>,
+  pub content: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditPreview {
+  pub images: Vec<RedditPreviewImageItem>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditPreviewImageItem {
+  pub source: RedditPreviewImage,
+  pub resolutions: Vec<RedditPreviewImage>,
+  pub variants: Option<RedditPreviewVariantItem>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditPreviewImageItemNoVars {
+  pub source: RedditPreviewImage,
+  pub resolutions: Vec<RedditPreviewImage>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditPreviewVariantItem {
+  pub gif: Option<RedditPreviewImageItemNoVars>,
+  pub mp4: Option<RedditPreviewImageItemNoVars>,
+}
+
+impl RedditPreviewImageItemNoVars {
+  pub fn get_srcset(&self) -> String {
+    let mut sizes = Vec::new();
+    for image in &self.resolutions {
+      sizes.push(image.to_srcset_value());
+    }
+    sizes.push(self.source.to_srcset_value());
+    sizes.join(", ")
+  }
+}
+
+impl RedditPreviewImageItem {
+  pub fn get_srcset(&self) -> String {
+    let mut sizes = Vec::new();
+    for image in &self.resolutions {
+      sizes.push(image.to_srcset_value());
+    }
+    sizes.push(self.source.to_srcset_value());
+    sizes.join(", ")
+  }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditPreviewImage {
+  pub url: String,
+  pub width: i32,
+  pub height: i32,
+}
+
+impl RedditPreviewImage {
+  pub fn to_srcset_value(&self) -> String {
+    format!("{} {}w", self.url.replace("&amp;", "&"), self.width)
+  }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditGalleryItemSource {
+  pub x: i32,
+  pub y: i32,
+  pub u: String,
+}
+
+impl RedditGalleryItemSource {
+  pub fn to_srcset_value(&self) -> String {
+    format!("{} {}w", self.u.replace("&amp;", "&"), self.x)
+  }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditGalleryData {
+  pub items: Vec<RedditGalleryDataItem>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditGalleryDataItem {
+  pub media_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedditGalleryItem {
+  pub s: RedditGalleryItemSource,
+  pub p: Vec<RedditGalleryItemSource>,
+}
+
+impl RedditGalleryItem {
+  pub fn get_srcset(&self) -> String {
+    let mut sizes = Vec::new();
+    for image in &self.p {
+      sizes.push(image.to_srcset_value());
+    }
+    sizes.push(self.s.to_srcset_value());
+    sizes.join(", ")
+  }
+}
+/// Picture of post `name` at `url` for tests, other fields left empty.
+#[cfg(test)]
+pub(crate) fn test_picture(name: &str, url: &str) -> RedditItem {
+  RedditItem {
+    title: String::new(),
+    title_url: String::new(),
+    name: name.to_string(),
+    crosspost_parent: None,
+    gallery: false,
+    author: String::new(),
+    flair: String::new(),
+    domain: String::new(),
+    item: RedditItemType::Picture { source_set: String::new(), url: url.to_string(), width: 0, height: 0 },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// `media_metadata` entry of gallery picture `id`.
+  fn gallery_picture(id: &str) -> String {
+    format!(r#""{}": {{"s": {{"x": 10, "y": 10, "u": "https://i.redd.it/{}.jpg"}}, "p": []}}"#, id, id)
+  }
+
+  fn listing(gallery_data: &str) -> RedditListingResponse {
+    let json = format!(r#"{{"data": {{"children": [{{"kind": "t3", "data": {{
+      "title": "t", "permalink": "/r/a/comments/x", "name": "t3_x", "url": "https://www.reddit.com/gallery/x",
+      "media_metadata": {{{}, {}, {}}}{}
+    }}}}]}}}}"#, gallery_picture("b"), gallery_picture("c"), gallery_picture("a"), gallery_data);
+    serde_json::from_str(&json).unwrap()
+  }
+
+  fn urls(response: RedditListingResponse) -> Vec<String> {
+    let (items, _) = response.get_items(&PostThresholds::default(), 0.0).unwrap();
+    items.iter().map(|item| item.media_url().to_string()).collect()
+  }
+
+  #[test]
+  fn gallery_follows_gallery_data() {
+    let gallery_data = r#", "gallery_data": {"items": [{"media_id": "c"}, {"media_id": "a"}, {"media_id": "b"}]}"#;
+    assert_eq!(urls(listing(gallery_data)), ["https://i.redd.it/c.jpg", "https://i.redd.it/a.jpg", "https://i.redd.it/b.jpg"]);
+  }
+
+  #[test]
+  fn empty_listing_is_not_a_failure() {
+    let response: RedditListingResponse = serde_json::from_str(r#"{"data": {"children": []}}"#).unwrap();
+    assert_eq!(response.get_items(&PostThresholds::default(), 0.0), Some((Vec::new(), String::new())));
+    let response: RedditListingResponse = serde_json::from_str(r#"{"kind": "Listing"}"#).unwrap();
+    assert_eq!(response.get_items(&PostThresholds::default(), 0.0), None);
+  }
+
+  #[test]
+  fn gallery_without_gallery_data_goes_by_media_id() {
+    assert_eq!(urls(listing("")), ["https://i.redd.it/a.jpg", "https://i.redd.it/b.jpg", "https://i.redd.it/c.jpg"]);
+  }
+}
@@ -0,0 +1,65 @@
+/// Size the thumbnail is scaled to before hashing: one extra column so every
+/// row yields 8 left/right comparisons.
+pub const DHASH_WIDTH: u32 = 9;
+pub const DHASH_HEIGHT: u32 = 8;
+
+/// Difference hash of a `DHASH_WIDTH` x `DHASH_HEIGHT` RGBA image: one bit per
+/// pixel telling whether it is brighter than its right neighbour.
+pub fn dhash(rgba: &[u8]) -> Option<u64> {
+  let pixels = (DHASH_WIDTH * DHASH_HEIGHT) as usize;
+  if rgba.len() < pixels * 4 {
+    return None;
+  }
+  let luma = rgba.chunks(4)
+      .take(pixels)
+      .map(|p| (p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000)
+      .collect::<Vec<_>>();
+
+  let mut hash = 0u64;
+  for row in luma.chunks(DHASH_WIDTH as usize) {
+    for pair in row.windows(2) {
+      hash = (hash << 1) | (pair[0] > pair[1]) as u64;
+    }
+  }
+  Some(hash)
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+  (a ^ b).count_ones()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Grey `DHASH_WIDTH` x `DHASH_HEIGHT` image, `luma` giving each column's brightness.
+  fn image(luma: impl Fn(u32) -> u8) -> Vec<u8> {
+    (0..DHASH_HEIGHT)
+        .flat_map(|_| (0..DHASH_WIDTH).flat_map(|x| {
+          let value = luma(x);
+          [value, value, value, 255]
+        }).collect::<Vec<_>>())
+        .collect()
+  }
+
+  #[test]
+  fn hashes_brightness_gradients() {
+    assert_eq!(dhash(&image(|x| 255 - x as u8 * 20)), Some(u64::MAX));
+    assert_eq!(dhash(&image(|x| x as u8 * 20)), Some(0));
+    assert_eq!(dhash(&image(|_| 128)), Some(0));
+  }
+
+  #[test]
+  fn needs_a_whole_thumbnail() {
+    let mut rgba = image(|_| 0);
+    rgba.pop();
+    assert_eq!(dhash(&rgba), None);
+  }
+
+  #[test]
+  fn counts_differing_bits() {
+    assert_eq!(hamming_distance(0b1011, 0b0001), 2);
+    assert_eq!(hamming_distance(u64::MAX, 0), 64);
+    assert_eq!(hamming_distance(42, 42), 0);
+  }
+}
@@ -0,0 +1,90 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+use crate::RedditItem;
+
+/// Schema version written into every exported playlist. Bump it whenever the
+/// shape of `Playlist` or `RedditItem` changes and migrate in `from_json`.
+pub const PLAYLIST_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Playlist {
+  pub version: u32,
+  pub name: String,
+  pub items: Vec<RedditItem>,
+}
+
+#[derive(Deserialize)]
+struct PlaylistHeader {
+  version: u32,
+}
+
+#[derive(Debug)]
+pub enum PlaylistError {
+  Json(serde_json::Error),
+  UnsupportedVersion(u32),
+}
+
+impl fmt::Display for PlaylistError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      PlaylistError::Json(e) => write!(f, "invalid playlist: {}", e),
+      PlaylistError::UnsupportedVersion(version) => write!(f, "unsupported playlist version {}", version),
+    }
+  }
+}
+
+impl std::error::Error for PlaylistError {}
+
+impl From<serde_json::Error> for PlaylistError {
+  fn from(e: serde_json::Error) -> Self {
+    PlaylistError::Json(e)
+  }
+}
+
+impl Playlist {
+  pub fn new(name: String, items: Vec<RedditItem>) -> Self {
+    Self {
+      version: PLAYLIST_VERSION,
+      name,
+      items,
+    }
+  }
+
+  pub fn to_json(&self) -> Result<String, PlaylistError> {
+    Ok(serde_json::to_string_pretty(self)?)
+  }
+
+  pub fn from_json(json: &str) -> Result<Self, PlaylistError> {
+    let header: PlaylistHeader = serde_json::from_str(json)?;
+    match header.version {
+      PLAYLIST_VERSION => Ok(serde_json::from_str(json)?),
+      version => Err(PlaylistError::UnsupportedVersion(version)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_picture;
+
+  #[test]
+  fn round_trips_through_json() {
+    let playlist = Playlist::new("wall".to_string(), vec![test_picture("t3_x", "https://i.redd.it/x.jpg")]);
+    let json = playlist.to_json().unwrap();
+    assert_eq!(Playlist::from_json(&json).unwrap(), playlist);
+  }
+
+  #[test]
+  fn rejects_other_versions() {
+    let json = r#"{"version": 2, "name": "wall", "items": [], "added_later": true}"#;
+    assert!(matches!(Playlist::from_json(json), Err(PlaylistError::UnsupportedVersion(2))));
+  }
+
+  #[test]
+  fn rejects_invalid_json() {
+    assert!(matches!(Playlist::from_json("{}"), Err(PlaylistError::Json(_))));
+    assert!(matches!(Playlist::from_json(r#"{"version": 1, "name": "wall"}"#), Err(PlaylistError::Json(_))));
+  }
+}
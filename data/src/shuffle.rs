@@ -0,0 +1,142 @@
+/// Reproducible shuffled playback order over a list that only grows at the end.
+///
+/// The same seed over the same items always gives the same order, so a
+/// session can be replayed by entering its seed again.
+pub struct ShuffleOrder {
+  seed: u64,
+  state: u64,
+  order: Vec<usize>,
+}
+
+impl ShuffleOrder {
+  pub fn new(seed: u64) -> Self {
+    Self {
+      seed,
+      state: seed,
+      order: Vec::new(),
+    }
+  }
+
+  pub fn seed(&self) -> u64 {
+    self.seed
+  }
+
+  pub fn len(&self) -> usize {
+    self.order.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.order.is_empty()
+  }
+
+  /// Reshuffles the first `len` items from the seed.
+  pub fn reset(&mut self, len: usize) {
+    self.state = self.seed;
+    self.order.clear();
+    self.extend_to(len);
+  }
+
+  /// Covers `len` items, shuffling only the new ones so the part already
+  /// played stays put. A shorter list can't be patched and is reshuffled.
+  pub fn extend_to(&mut self, len: usize) {
+    if len < self.order.len() {
+      self.reset(len);
+      return;
+    }
+    let start = self.order.len();
+    self.order.extend(start..len);
+    for i in (start + 1..len).rev() {
+      let j = start + (self.next() % (i - start + 1) as u64) as usize;
+      self.order.swap(i, j);
+    }
+  }
+
+  /// Forgets item `index`, removed from the list, shifting later indices
+  /// down so the rest of the order is unchanged.
+  pub fn remove(&mut self, index: usize) {
+    self.order.retain(|&x| x != index);
+    for x in self.order.iter_mut() {
+      if *x > index {
+        *x -= 1;
+      }
+    }
+  }
+
+  /// Item index played at `position`.
+  pub fn get(&self, position: usize) -> Option<usize> {
+    self.order.get(position).copied()
+  }
+
+  /// Position at which item `index` is played.
+  pub fn position(&self, index: usize) -> Option<usize> {
+    self.order.iter().position(|&x| x == index)
+  }
+
+  /// splitmix64, good enough for shuffling and trivially reproducible.
+  fn next(&mut self) -> u64 {
+    self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = self.state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn order(shuffle: &ShuffleOrder) -> Vec<usize> {
+    (0..shuffle.len()).filter_map(|position| shuffle.get(position)).collect()
+  }
+
+  fn is_permutation(order: &[usize]) -> bool {
+    let mut sorted = order.to_vec();
+    sorted.sort_unstable();
+    sorted == (0..order.len()).collect::<Vec<_>>()
+  }
+
+  #[test]
+  fn same_seed_same_order() {
+    let mut first = ShuffleOrder::new(42);
+    let mut second = ShuffleOrder::new(42);
+    first.reset(20);
+    second.extend_to(20);
+    assert_eq!(order(&first), order(&second));
+    assert!(is_permutation(&order(&first)));
+    assert_ne!(order(&first), (0..20).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn extend_to_keeps_played_prefix() {
+    let mut shuffle = ShuffleOrder::new(7);
+    shuffle.extend_to(10);
+    let played = order(&shuffle);
+    shuffle.extend_to(25);
+    let extended = order(&shuffle);
+    assert_eq!(extended[..10], played[..]);
+    assert!(is_permutation(&extended));
+  }
+
+  #[test]
+  fn shorter_list_is_reshuffled() {
+    let mut shuffle = ShuffleOrder::new(7);
+    shuffle.extend_to(10);
+    shuffle.extend_to(4);
+    assert_eq!(shuffle.len(), 4);
+    assert!(is_permutation(&order(&shuffle)));
+  }
+
+  #[test]
+  fn remove_shifts_later_indices() {
+    let mut shuffle = ShuffleOrder::new(3);
+    shuffle.extend_to(8);
+    let expected: Vec<usize> = order(&shuffle).into_iter()
+        .filter(|&index| index != 5)
+        .map(|index| if index > 5 { index - 1 } else { index })
+        .collect();
+    shuffle.remove(5);
+    assert_eq!(order(&shuffle), expected);
+    assert_eq!(shuffle.position(expected[0]), Some(0));
+  }
+}
@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const MAX_ENTRIES: usize = 256;
+
+/// Small in-memory cache of upstream responses keyed by their url.
+pub struct ResponseCache {
+  ttl: Duration,
+  entries: Mutex<HashMap<String, (Instant, Vec<u8>)>>,
+}
+
+impl ResponseCache {
+  pub fn new(ttl: Duration) -> Self {
+    Self {
+      ttl,
+      entries: Mutex::new(HashMap::new()),
+    }
+  }
+
+  pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+    let entries = self.entries.lock().unwrap();
+    entries.get(key)
+        .filter(|(stored, _)| stored.elapsed() < self.ttl)
+        .map(|(_, body)| body.clone())
+  }
+
+  pub fn insert(&self, key: String, body: Vec<u8>) {
+    let mut entries = self.entries.lock().unwrap();
+    let ttl = self.ttl;
+    entries.retain(|_, (stored, _)| stored.elapsed() < ttl);
+    if entries.len() >= MAX_ENTRIES {
+      let oldest = entries.iter()
+          .min_by_key(|(_, (stored, _))| *stored)
+          .map(|(key, _)| key.clone());
+      if let Some(oldest) = oldest {
+        entries.remove(&oldest);
+      }
+    }
+    entries.insert(key, (Instant::now(), body));
+  }
+}
@@ -0,0 +1,85 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Resolves a request path to a file in `root`, falling back to `index.html`
+/// for client side routes like `/r/earthporn`.
+pub fn resolve(root: &Path, path: &str) -> Option<PathBuf> {
+  let relative = Path::new(path.trim_start_matches('/'));
+  if relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+    return None;
+  }
+
+  let file = root.join(relative);
+  if file.is_file() {
+    return Some(file);
+  }
+  if relative.extension().is_none() {
+    let index = root.join("index.html");
+    if index.is_file() {
+      return Some(index);
+    }
+  }
+  None
+}
+
+pub fn read(file: &Path) -> Option<Vec<u8>> {
+  fs::read(file).ok()
+}
+
+pub fn content_type(file: &Path) -> &'static str {
+  match file.extension().and_then(|x| x.to_str()) {
+    Some("html") => "text/html; charset=utf-8",
+    Some("css") => "text/css; charset=utf-8",
+    Some("js") => "application/javascript",
+    Some("wasm") => "application/wasm",
+    Some("json") => "application/json",
+    Some("ts") => "application/typescript",
+    Some("png") => "image/png",
+    Some("svg") => "image/svg+xml",
+    Some("ico") => "image/x-icon",
+    _ => "application/octet-stream",
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Fresh directory with `index.html` and `app.js`, removed when dropped.
+  struct Root(PathBuf);
+
+  impl Root {
+    fn new(name: &str) -> Self {
+      let dir = std::env::temp_dir().join(format!("reddit-galery-files-{}-{}", name, std::process::id()));
+      fs::create_dir_all(&dir).unwrap();
+      fs::write(dir.join("index.html"), "<html></html>").unwrap();
+      fs::write(dir.join("app.js"), "").unwrap();
+      Root(dir)
+    }
+  }
+
+  impl Drop for Root {
+    fn drop(&mut self) {
+      let _ = fs::remove_dir_all(&self.0);
+    }
+  }
+
+  #[test]
+  fn serves_files_and_client_routes() {
+    let root = Root::new("routes");
+    assert_eq!(resolve(&root.0, "/app.js"), Some(root.0.join("app.js")));
+    assert_eq!(resolve(&root.0, "/"), Some(root.0.join("index.html")));
+    assert_eq!(resolve(&root.0, "/r/earthporn"), Some(root.0.join("index.html")));
+    assert_eq!(resolve(&root.0, "/missing.js"), None);
+  }
+
+  #[test]
+  fn stays_inside_root() {
+    let root = Root::new("traversal");
+    assert_eq!(resolve(&root.0, "/../index.html"), None);
+    assert_eq!(resolve(&root.0, "/r/../../etc/passwd"), None);
+    assert_eq!(resolve(&root.0, "/./app.js"), None);
+    // Leading slashes are all stripped, so this is a client route in `root`.
+    assert_eq!(resolve(&root.0, "//etc/passwd"), Some(root.0.join("index.html")));
+  }
+}
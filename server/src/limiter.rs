@@ -0,0 +1,33 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Spaces out upstream requests so that at most one starts per `interval`.
+pub struct RateLimiter {
+  interval: Duration,
+  next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+  pub fn new(interval: Duration) -> Self {
+    Self {
+      interval,
+      next_slot: Mutex::new(Instant::now()),
+    }
+  }
+
+  /// Blocks the calling thread until it is allowed to hit reddit.
+  pub fn wait(&self) {
+    let slot = {
+      let mut next_slot = self.next_slot.lock().unwrap();
+      let now = Instant::now();
+      let slot = if *next_slot > now { *next_slot } else { now };
+      *next_slot = slot + self.interval;
+      slot
+    };
+    let now = Instant::now();
+    if slot > now {
+      thread::sleep(slot - now);
+    }
+  }
+}
@@ -0,0 +1,208 @@
+mod cache;
+mod files;
+mod limiter;
+mod upstream;
+
+use anyhow::{anyhow, Error};
+use log::{error, info, warn};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tiny_http::{Header, Method, Request, Response, Server};
+use upstream::Upstream;
+
+const USAGE: &str = "usage: reddit-galery-server [--bind ADDR] [--static DIR] [--cache-ttl SECS] [--min-interval-ms MS] [--workers N]";
+const API_PREFIX: &str = "/api";
+const AUTOCOMPLETE_PATH: &str = "/api/subreddit_autocomplete";
+const LISTING_SUFFIX: &str = "/.json";
+
+struct Config {
+  bind: String,
+  static_dir: PathBuf,
+  cache_ttl: Duration,
+  min_interval: Duration,
+  workers: usize,
+}
+
+impl Config {
+  fn from_args() -> Result<Self, Error> {
+    let mut config = Self {
+      bind: "127.0.0.1:8080".to_string(),
+      static_dir: PathBuf::from("static"),
+      cache_ttl: Duration::from_secs(60),
+      min_interval: Duration::from_millis(1000),
+      workers: 4,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+      let mut value = || args.next().ok_or_else(|| anyhow!("missing value for {}\n{}", arg, USAGE));
+      match arg.as_str() {
+        "--bind" => config.bind = value()?,
+        "--static" => config.static_dir = PathBuf::from(value()?),
+        "--cache-ttl" => config.cache_ttl = Duration::from_secs(value()?.parse()?),
+        "--min-interval-ms" => config.min_interval = Duration::from_millis(value()?.parse()?),
+        "--workers" => config.workers = value()?.parse::<usize>()?.max(1),
+        _ => return Err(anyhow!("unknown argument {}\n{}", arg, USAGE)),
+      }
+    }
+    Ok(config)
+  }
+}
+
+/// Only let through paths that look like reddit listings, so the proxy can't be
+/// pointed at anything else.
+fn is_listing_path(path: &str) -> bool {
+  path.starts_with('/') && path.len() > 1
+      && path.chars().all(|c| c.is_ascii_alphanumeric() || "/_-+".contains(c))
+      && !path.contains("//")
+}
+
+/// Query made of plain characters and `%XX` escapes, which searches need for
+/// spaces and non-ASCII text. Escapes are passed on to reddit still encoded.
+fn is_safe_query(query: &str) -> bool {
+  let bytes = query.as_bytes();
+  let mut index = 0;
+  while index < bytes.len() {
+    match bytes[index] {
+      b'%' => {
+        if !bytes.get(index + 1..index + 3).is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit)) {
+          return false;
+        }
+        index += 3;
+      }
+      c if c.is_ascii_alphanumeric() || b"_-=&+.".contains(&c) => index += 1,
+      _ => return false,
+    }
+  }
+  true
+}
+
+fn header(name: &str, value: &str) -> Header {
+  Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn json(body: Vec<u8>) -> Response<std::io::Cursor<Vec<u8>>> {
+  Response::from_data(body).with_header(header("Content-Type", "application/json"))
+}
+
+fn status(code: u16, text: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+  Response::from_string(text).with_status_code(code)
+}
+
+fn handle(request: Request, config: &Config, upstream: &Upstream) {
+  let url = request.url().to_string();
+  let (path, query) = match url.find('?') {
+    Some(index) => (&url[..index], &url[index + 1..]),
+    None => (url.as_str(), ""),
+  };
+
+  let response = if *request.method() != Method::Get {
+    status(405, "method not allowed")
+  } else if path == AUTOCOMPLETE_PATH {
+    if !is_safe_query(query) {
+      status(400, "bad query")
+    } else {
+      match upstream.subreddit_autocomplete(query) {
+        Ok(body) => json(body),
+        Err(e) => {
+          warn!("{}: {}", url, e);
+          status(502, "upstream failed")
+        }
+      }
+    }
+  } else if let Some(listing) = path.strip_prefix(API_PREFIX) {
+    let listing = listing.trim_end_matches('/');
+    if !is_listing_path(listing) || !is_safe_query(query) {
+      status(400, "bad listing")
+    } else {
+      match upstream.items(listing, query).and_then(|page| Ok(serde_json::to_vec(&page)?)) {
+        Ok(body) => json(body),
+        Err(e) => {
+          warn!("{}: {}", url, e);
+          status(502, "upstream failed")
+        }
+      }
+    }
+  } else if let Some(listing) = path.strip_suffix(LISTING_SUFFIX) {
+    if !is_listing_path(listing) || !is_safe_query(query) {
+      status(400, "bad listing")
+    } else {
+      match upstream.listing(listing, query) {
+        Ok(body) => json(body),
+        Err(e) => {
+          warn!("{}: {}", url, e);
+          status(502, "upstream failed")
+        }
+      }
+    }
+  } else {
+    match files::resolve(&config.static_dir, path) {
+      Some(file) => match files::read(&file) {
+        Some(body) => Response::from_data(body).with_header(header("Content-Type", files::content_type(&file))),
+        None => status(500, "unreadable file"),
+      },
+      None => status(404, "not found"),
+    }
+  };
+
+  if let Err(e) = request.respond(response) {
+    error!("{}: {}", url, e);
+  }
+}
+
+fn main() -> Result<(), Error> {
+  env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+  let config = Arc::new(Config::from_args()?);
+  let upstream = Arc::new(Upstream::new(config.cache_ttl, config.min_interval));
+  let server = Arc::new(Server::http(&config.bind).map_err(|e| anyhow!("{}", e))?);
+  info!("serving {} on http://{}", config.static_dir.display(), config.bind);
+
+  let workers: Vec<_> = (0..config.workers).map(|_| {
+    let server = server.clone();
+    let config = config.clone();
+    let upstream = upstream.clone();
+    thread::spawn(move || {
+      for request in server.incoming_requests() {
+        handle(request, &config, &upstream);
+      }
+    })
+  }).collect();
+
+  for worker in workers {
+    let _ = worker.join();
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn accepts_plain_and_escaped_queries() {
+    assert!(is_safe_query(""));
+    assert!(is_safe_query("q=cats+dogs&restrict_sr=on&sort=top&t=week"));
+    assert!(is_safe_query("q=red%20panda&after=t3_abc"));
+    assert!(is_safe_query("q=%C5%BEirafa"));
+  }
+
+  #[test]
+  fn rejects_broken_escapes_and_other_characters() {
+    assert!(!is_safe_query("q=100%"));
+    assert!(!is_safe_query("q=%2"));
+    assert!(!is_safe_query("q=%zz"));
+    assert!(!is_safe_query("q=a b"));
+    assert!(!is_safe_query("q=a/../b"));
+    assert!(!is_safe_query("q=a#b"));
+  }
+
+  #[test]
+  fn listing_paths() {
+    assert!(is_listing_path("/r/earthporn+skyporn/top"));
+    assert!(!is_listing_path("/"));
+    assert!(!is_listing_path("/r/../etc"));
+    assert!(!is_listing_path("//evil.com/r"));
+  }
+}
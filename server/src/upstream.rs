@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Error};
+use log::{debug, info};
+use reddit_data::{PostThresholds, RedditItemsPage, RedditListingResponse};
+use std::io::Read;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::cache::ResponseCache;
+use crate::limiter::RateLimiter;
+
+const REDDIT: &str = "https://www.reddit.com";
+const USER_AGENT: &str = concat!("reddit-galery-server/", env!("CARGO_PKG_VERSION"));
+const RANDOM_SUFFIX: &str = "/random";
+const MAX_BODY: u64 = 16 * 1024 * 1024;
+
+/// Fetches listings from reddit, going through the cache and the rate limiter.
+pub struct Upstream {
+  agent: ureq::Agent,
+  cache: ResponseCache,
+  limiter: RateLimiter,
+}
+
+impl Upstream {
+  pub fn new(cache_ttl: Duration, min_interval: Duration) -> Self {
+    Self {
+      agent: ureq::AgentBuilder::new()
+          .user_agent(USER_AGENT)
+          .timeout(Duration::from_secs(20))
+          .build(),
+      cache: ResponseCache::new(cache_ttl),
+      limiter: RateLimiter::new(min_interval),
+    }
+  }
+
+  /// Raw listing json for `path` (e.g. `/r/earthporn`) as reddit returned it.
+  pub fn listing(&self, path: &str, query: &str) -> Result<Vec<u8>, Error> {
+    let url = if query.is_empty() {
+      format!("{}{}/.json", REDDIT, path)
+    } else {
+      format!("{}{}/.json?{}", REDDIT, path, query)
+    };
+    // Every request for a random post should give a different one.
+    self.fetch(url, !path.ends_with(RANDOM_SUFFIX))
+  }
+
+  /// Subreddit name suggestions for a partially typed name, as reddit returned them.
+  pub fn subreddit_autocomplete(&self, query: &str) -> Result<Vec<u8>, Error> {
+    self.fetch(format!("{}/api/subreddit_autocomplete.json?{}", REDDIT, query), true)
+  }
+
+  fn fetch(&self, url: String, cacheable: bool) -> Result<Vec<u8>, Error> {
+    if let Some(body) = self.cache.get(&url).filter(|_| cacheable) {
+      debug!("cache hit {}", url);
+      return Ok(body);
+    }
+
+    self.limiter.wait();
+    info!("fetching {}", url);
+    let response = self.agent.get(&url).call()?;
+    let mut body = Vec::new();
+    response.into_reader().take(MAX_BODY).read_to_end(&mut body)?;
+    if cacheable {
+      self.cache.insert(url, body.clone());
+    }
+    Ok(body)
+  }
+
+  /// Listing for `path` run through the same extraction as the wasm client,
+  /// taking the client's score and age thresholds out of `query`.
+  pub fn items(&self, path: &str, query: &str) -> Result<RedditItemsPage, Error> {
+    let (thresholds, query) = PostThresholds::from_query(query);
+    let body = self.listing(path, &query)?;
+    let listings: RedditListingResponse = serde_json::from_slice(&body)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+    let (items, after) = listings.get_items(&thresholds, now)
+        .ok_or_else(|| anyhow!("{} is not a listing", path))?;
+    Ok(RedditItemsPage { items, after })
+  }
+}
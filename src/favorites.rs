@@ -0,0 +1,130 @@
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{IdbDatabase, IdbRequest, IdbTransactionMode};
+use yew::Callback;
+use log::error;
+use serde_derive::{Deserialize, Serialize};
+use reddit_data::RedditItem;
+
+const DB_NAME: &str = "reddit-galery";
+const DB_VERSION: u32 = 1;
+const STORE: &str = "favorites";
+
+/// Calls `done` once `request` either succeeds or fails.
+fn on_done(request: &IdbRequest, done: impl FnOnce(Result<JsValue, JsValue>) + 'static) {
+  let req = request.clone();
+  let handler = Closure::once_into_js(move |_: web_sys::Event| {
+    done(match req.error() {
+      Ok(None) => req.result(),
+      Ok(Some(e)) => Err(e.into()),
+      Err(e) => Err(e),
+    })
+  });
+  request.set_onsuccess(Some(handler.unchecked_ref()));
+  request.set_onerror(Some(handler.unchecked_ref()));
+}
+
+/// Saved item and where it goes among the others.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct Favorite {
+  /// Favourites play in ascending `position`, gaps are fine.
+  pub(crate) position: f64,
+  pub(crate) item: RedditItem,
+}
+
+impl Favorite {
+  /// Position that sorts between `before` and `after`, either may be missing
+  /// at the ends.
+  pub(crate) fn position_between(before: Option<&Favorite>, after: Option<&Favorite>) -> f64 {
+    match (before, after) {
+      (Some(before), Some(after)) => (before.position + after.position) / 2.0,
+      (Some(before), None) => before.position + 1.0,
+      (None, Some(after)) => after.position - 1.0,
+      (None, None) => 0.0,
+    }
+  }
+
+  /// Sorts by position, ties by media url so every tab agrees on the order.
+  pub(crate) fn sort(favorites: &mut [Favorite]) {
+    favorites.sort_by(|a, b| a.position.total_cmp(&b.position).then_with(|| a.item.media_url().cmp(b.item.media_url())));
+  }
+
+  fn to_record(&self) -> Result<JsValue, JsValue> {
+    serde_json::to_string(self)
+        .map(|json| JsValue::from_str(&json))
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+  }
+}
+
+/// Favourite items kept in IndexedDB, one json record per item keyed by its
+/// media url, so adding, moving or removing one writes only that record.
+#[derive(Clone)]
+pub(crate) struct FavoritesDb {
+  db: IdbDatabase,
+}
+
+impl FavoritesDb {
+  pub(crate) fn open(callback: Callback<Result<FavoritesDb, JsValue>>) -> Result<(), JsValue> {
+    let factory = web_sys::window()
+        .ok_or_else(|| JsValue::from_str("window not available"))?
+        .indexed_db()?
+        .ok_or_else(|| JsValue::from_str("indexedDB not available"))?;
+    let request = factory.open_with_u32(DB_NAME, DB_VERSION)?;
+
+    let upgrade_request = request.clone();
+    let upgrade = Closure::once_into_js(move |_: web_sys::Event| {
+      if let Ok(db) = upgrade_request.result() {
+        let db: IdbDatabase = db.unchecked_into();
+        if !db.object_store_names().contains(STORE) {
+          if let Err(e) = db.create_object_store(STORE) {
+            error!("{:?}", e);
+          }
+        }
+      }
+    });
+    request.set_onupgradeneeded(Some(upgrade.unchecked_ref()));
+
+    on_done(&request, move |result| {
+      callback.emit(result.map(|db| FavoritesDb { db: db.unchecked_into() }));
+    });
+    Ok(())
+  }
+
+  /// Loads the favourites sorted by position.
+  pub(crate) fn load(&self, callback: Callback<Vec<Favorite>>) -> Result<(), JsValue> {
+    let transaction = self.db.transaction_with_str(STORE)?;
+    let request = transaction.object_store(STORE)?.get_all()?;
+    on_done(&request, move |result| {
+      let mut favorites = Vec::new();
+      match result {
+        Ok(records) => {
+          for record in js_sys::Array::from(&records).iter() {
+            match record.as_string().map(|json| serde_json::from_str::<Favorite>(&json)) {
+              Some(Ok(favorite)) => favorites.push(favorite),
+              Some(Err(e)) => error!("skipping favorite: {}", e),
+              None => error!("skipping favorite: not a string"),
+            }
+          }
+        }
+        Err(e) => error!("{:?}", e),
+      }
+      Favorite::sort(&mut favorites);
+      callback.emit(favorites);
+    });
+    Ok(())
+  }
+
+  /// Adds `favorite`, or moves it if it is saved already.
+  pub(crate) fn put(&self, favorite: &Favorite) -> Result<(), JsValue> {
+    let transaction = self.db.transaction_with_str_and_mode(STORE, IdbTransactionMode::Readwrite)?;
+    let key = JsValue::from_str(favorite.item.media_url());
+    transaction.object_store(STORE)?.put_with_key(&favorite.to_record()?, &key)?;
+    Ok(())
+  }
+
+  pub(crate) fn delete(&self, media_url: &str) -> Result<(), JsValue> {
+    let transaction = self.db.transaction_with_str_and_mode(STORE, IdbTransactionMode::Readwrite)?;
+    transaction.object_store(STORE)?.delete(&JsValue::from_str(media_url))?;
+    Ok(())
+  }
+}
@@ -0,0 +1,85 @@
+use serde_derive::{Deserialize, Serialize};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{Blob, ImageBitmap, OffscreenCanvas, OffscreenCanvasRenderingContext2d, WorkerGlobalScope};
+use yew::agent::{Agent, AgentLink, HandlerId, Public};
+use log::warn;
+use reddit_data::{dhash, DHASH_HEIGHT, DHASH_WIDTH};
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct HashRequest {
+  /// Media url of the item, handed back untouched in the response.
+  pub(crate) key: String,
+  pub(crate) thumbnail_url: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct HashResponse {
+  pub(crate) key: String,
+  pub(crate) hash: Option<u64>,
+}
+
+pub(crate) enum Msg {
+  Hashed(HandlerId, HashResponse),
+}
+
+/// Computes perceptual hashes of picture thumbnails off the main thread.
+pub(crate) struct HashWorker {
+  link: AgentLink<Self>,
+}
+
+async fn hash_image(url: &str) -> Result<Option<u64>, JsValue> {
+  let scope: WorkerGlobalScope = js_sys::global().unchecked_into();
+  let response: web_sys::Response = JsFuture::from(scope.fetch_with_str(url)).await?.unchecked_into();
+  if !response.ok() {
+    return Ok(None);
+  }
+  let blob: Blob = JsFuture::from(response.blob()?).await?.unchecked_into();
+  let bitmap: ImageBitmap = JsFuture::from(scope.create_image_bitmap_with_blob(&blob)?).await?.unchecked_into();
+
+  let (width, height) = (DHASH_WIDTH as f64, DHASH_HEIGHT as f64);
+  let canvas = OffscreenCanvas::new(DHASH_WIDTH, DHASH_HEIGHT)?;
+  let context: OffscreenCanvasRenderingContext2d = canvas.get_context("2d")?
+      .ok_or_else(|| JsValue::from_str("2d context not available"))?
+      .unchecked_into();
+  context.draw_image_with_image_bitmap_and_dw_and_dh(&bitmap, 0.0, 0.0, width, height)?;
+  bitmap.close();
+  let pixels = context.get_image_data(0.0, 0.0, width, height)?.data();
+  Ok(dhash(&pixels))
+}
+
+impl Agent for HashWorker {
+  type Reach = Public<Self>;
+  type Message = Msg;
+  type Input = HashRequest;
+  type Output = HashResponse;
+
+  fn create(link: AgentLink<Self>) -> Self {
+    Self { link }
+  }
+
+  fn update(&mut self, msg: Self::Message) {
+    match msg {
+      Msg::Hashed(who, response) => self.link.respond(who, response),
+    }
+  }
+
+  fn handle_input(&mut self, request: Self::Input, who: HandlerId) {
+    let link = self.link.clone();
+    spawn_local(async move {
+      let hash = match hash_image(&request.thumbnail_url).await {
+        Ok(hash) => hash,
+        Err(e) => {
+          warn!("{}: {:?}", request.thumbnail_url, e);
+          None
+        }
+      };
+      link.send_message(Msg::Hashed(who, HashResponse { key: request.key, hash }));
+    });
+  }
+
+  /// The worker runs a `no-modules` build of this crate, see the readme.
+  fn name_of_resource() -> &'static str {
+    "worker/wasm.js"
+  }
+}
@@ -0,0 +1,89 @@
+use std::collections::{HashSet, VecDeque};
+use yew::format::Json;
+use yew::services::storage::StorageService;
+use anyhow::Error;
+use reddit_data::hamming_distance;
+
+const SEEN_KEY: &str = "SEEN_KEY";
+const SEEN_HASHES_KEY: &str = "SEEN_HASHES_KEY";
+/// Oldest entries are forgotten past this, so local storage doesn't fill up.
+const MAX_SEEN: usize = 10_000;
+
+/// Fullnames of posts that were on screen long enough to count as seen, along
+/// with perceptual hashes of their pictures when those were computed.
+#[derive(Default)]
+pub(crate) struct SeenHistory {
+  order: VecDeque<String>,
+  names: HashSet<String>,
+  hashes: VecDeque<u64>,
+}
+
+impl SeenHistory {
+  pub(crate) fn restore(storage: &StorageService) -> Self {
+    let mut history = Self::default();
+    let Json(names): Json<Result<Vec<String>, Error>> = storage.restore(SEEN_KEY);
+    if let Ok(names) = names {
+      for name in names {
+        history.insert(name);
+      }
+    }
+    let Json(hashes): Json<Result<Vec<u64>, Error>> = storage.restore(SEEN_HASHES_KEY);
+    if let Ok(hashes) = hashes {
+      for hash in hashes {
+        history.insert_hash(hash);
+      }
+    }
+    history
+  }
+
+  pub(crate) fn store(&self, storage: &mut StorageService) {
+    storage.store(SEEN_KEY, Json(&self.order));
+    storage.store(SEEN_HASHES_KEY, Json(&self.hashes));
+  }
+
+  pub(crate) fn contains(&self, name: &str) -> bool {
+    self.names.contains(name)
+  }
+
+  /// Returns whether `name` wasn't seen before.
+  pub(crate) fn insert(&mut self, name: String) -> bool {
+    if name.is_empty() || self.names.contains(&name) {
+      return false;
+    }
+    if self.order.len() >= MAX_SEEN {
+      if let Some(oldest) = self.order.pop_front() {
+        self.names.remove(&oldest);
+      }
+    }
+    self.names.insert(name.clone());
+    self.order.push_back(name);
+    true
+  }
+
+  /// Returns whether `hash` wasn't stored before.
+  pub(crate) fn insert_hash(&mut self, hash: u64) -> bool {
+    if self.hashes.contains(&hash) {
+      return false;
+    }
+    if self.hashes.len() >= MAX_SEEN {
+      self.hashes.pop_front();
+    }
+    self.hashes.push_back(hash);
+    true
+  }
+
+  /// Hamming distance from `hash` to the most similar seen picture.
+  pub(crate) fn closest_hash(&self, hash: u64) -> Option<u32> {
+    self.hashes.iter().map(|seen| hamming_distance(*seen, hash)).min()
+  }
+
+  pub(crate) fn clear(&mut self) {
+    self.order.clear();
+    self.names.clear();
+    self.hashes.clear();
+  }
+
+  pub(crate) fn len(&self) -> usize {
+    self.order.len()
+  }
+}
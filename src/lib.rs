@@ -1,27 +1,96 @@
-#![recursion_limit = "1024"]
+#![recursion_limit = "2048"]
 
-#[macro_use]
-extern crate lazy_static;
-
-mod data;
+mod favorites;
+mod hasher;
+mod history;
+mod multis;
 mod player;
+mod playlist;
+mod rules;
+mod settings;
+mod switcher;
+mod sync;
 
 use wasm_bindgen::prelude::*;
 use yew::prelude::*;
-use yew::services::{IntervalService, Task};
+use yew::agent::{Bridge, Bridged, Threaded};
+use yew::services::{IntervalService, Task, TimeoutService};
+use yew::services::timeout::TimeoutTask;
 use yew::services::storage::{Area, StorageService};
 use yew::services::fetch::{FetchService, Request, Response, FetchTask};
-use log::error;
+use yew::services::reader::{FileData, ReaderService, ReaderTask};
+use log::{error, info, warn};
 use std::time::Duration;
 use yew::format::{Nothing, Json};
 use anyhow::Error;
-use data::*;
+use reddit_data::*;
 use player::Player;
+use switcher::Switcher;
+use multis::{MultiEditor, Multireddit, MULTI_PREFIX};
+use settings::{scope_of, PlaybackOrder, Preferences, RepostMode, Settings, SettingsPanel, Transition};
+use favorites::{Favorite, FavoritesDb};
+use history::SeenHistory;
+use sync::{SyncMessage, TabSync};
+use hasher::{HashRequest, HashResponse, HashWorker};
+use std::collections::{HashMap, HashSet};
+use web_sys::HtmlImageElement;
+
+/// Where `Model::items` come from.
+#[derive(Clone, PartialEq)]
+enum Source {
+  /// Listing at `Model::url`, paged in as the slideshow advances.
+  Reddit,
+  /// Imported playlist file, fully loaded up front.
+  Playlist(String),
+  /// Saved favourites, played at `FAVORITES_PATH`.
+  Favorites,
+}
+
+fn random_seed() -> u64 {
+  (js_sys::Math::random() * u32::MAX as f64) as u64
+}
+
+/// Post fullname and index within its gallery, as in `#t3_abc123/2`.
+fn parse_location_hash(hash: &str) -> Option<(String, usize)> {
+  let hash = hash.trim_start_matches('#');
+  let (name, sub_index) = match hash.split_once('/') {
+    Some((name, sub_index)) => (name, sub_index.parse().ok()?),
+    None => (hash, 0),
+  };
+  if name.is_empty() {
+    None
+  } else {
+    Some((name.to_string(), sub_index))
+  }
+}
+
+/// What a listing route had loaded, kept so going back to it is instant.
+struct RouteState {
+  items: Vec<RedditItem>,
+  after: Option<String>,
+  current_index: i32,
+  dedup: Deduplicator,
+  multi_afters: Vec<Option<String>>,
+  shuffle: ShuffleOrder,
+}
+
+/// Width over height of the browser window, for the "match screen" filter.
+fn screen_aspect() -> f64 {
+  web_sys::window()
+      .and_then(|window| Some(window.inner_width().ok()?.as_f64()? / window.inner_height().ok()?.as_f64()?))
+      .filter(|aspect| aspect.is_finite())
+      .unwrap_or(0.0)
+}
+
+/// Whether the user asked the system to keep animations to a minimum.
+fn prefers_reduced_motion() -> bool {
+  web_sys::window()
+      .and_then(|window| window.match_media("(prefers-reduced-motion: reduce)").ok().flatten())
+      .is_some_and(|query| query.matches())
+}
 
 struct Model {
   link: ComponentLink<Self>,
-  timeout: u64,
-  timeout_enable: bool,
   items: Vec<RedditItem>,
   current_index: i32,
   url: String,
@@ -33,11 +102,74 @@ struct Model {
   after: Option<String>,
   ft: Option<FetchTask>,
   storage: Option<StorageService>,
+  source: Source,
+  reader_task: Option<ReaderTask>,
+  favorites: Vec<Favorite>,
+  favorites_db: Option<FavoritesDb>,
+  /// Media urls of favourites removed before the database loaded, `None`
+  /// once it has.
+  favorites_removed: Option<Vec<String>>,
+  history: SeenHistory,
+  shown_name: Option<String>,
+  seen_job: Option<TimeoutTask>,
+  dedup: Deduplicator,
+  hasher: Option<Box<dyn Bridge<HashWorker>>>,
+  /// Perceptual hashes of loaded pictures by media url.
+  hashes: HashMap<String, u64>,
+  reposts: HashSet<String>,
+  /// The user is pausing or scrubbing the current video, hold the slideshow.
+  video_interacting: bool,
+  /// Media urls of items that failed to load, skipped when navigating.
+  broken: HashSet<String>,
+  /// The player is in picture-in-picture, keep it alive between videos.
+  pip: bool,
+  /// Sources of the most recently shown video, kept playing in
+  /// picture-in-picture while pictures are on screen.
+  last_video: Option<Vec<VideoSource>>,
+  /// Index and item currently on screen, to notice when it changes.
+  shown: Option<(i32, RedditItem)>,
+  /// Item leaving the screen and whether we moved forward, kept mounted
+  /// until `transition_job` fires.
+  outgoing: Option<(RedditItem, bool)>,
+  transition_job: Option<TimeoutTask>,
+  shuffle: ShuffleOrder,
+  /// Random posts in a row that gave nothing to show.
+  random_misses: u32,
+  /// The tab is in the background, hold the slideshow.
+  page_hidden: bool,
+  /// The mouse is over the toolbox, hold the slideshow.
+  toolbox_hover: bool,
+  /// Reports `visibilitychange`, kept alive for the lifetime of the page.
+  _visibility_listener: Closure<dyn Fn(Event)>,
+  /// Post from the url hash still being looked for, and pages searched so far.
+  target: Option<(String, usize)>,
+  target_pages: usize,
+  /// Hash last written to the url.
+  location_hash: String,
+  /// Query string of the route without the `?`, e.g. a search.
+  query: String,
+  /// Most recently left routes last.
+  route_cache: Vec<(String, RouteState)>,
+  multis: Vec<Multireddit>,
+  /// Per subreddit `after` cursors of an interleaved multi, empty once a
+  /// subreddit has nothing more.
+  multi_afters: Vec<Option<String>>,
+  /// Pages of an interleaved multi arrived so far for the load in progress.
+  multi_pages: Vec<Option<Vec<RedditItem>>>,
+  multi_fts: Vec<FetchTask>,
+  /// Some subreddit of the load in progress answered, as opposed to all failing.
+  multi_answered: bool,
+  /// `prefs.keyword_rules` compiled.
+  keyword_filter: KeywordFilter,
+  settings: Settings,
+  /// Preferences in effect on the current route, see `Settings::for_scope`.
+  prefs: Preferences,
+  _popstate_listener: Closure<dyn Fn(Event)>,
+  /// Other open tabs, told about changes to settings, favourites and history.
+  sync: Option<TabSync>,
 }
 
 enum Msg {
-  TimeoutToggle,
-  TimeoutSet(ChangeData),
   SetIndex(i32),
   NextPicture,
   Tick,
@@ -45,65 +177,692 @@ enum Msg {
   ItemsFailed,
   ItemsLoaded((Vec<RedditItem>, String)),
   LoadItems,
+  ExportPlaylist,
+  ImportPlaylist(ChangeData),
+  PlaylistRead(FileData),
+  FavoritesOpened(Result<FavoritesDb, JsValue>),
+  FavoritesLoaded(Vec<Favorite>),
+  ToggleFavorite,
+  MoveFavorite(i32),
+  MarkSeen(String),
+  ClearHistory,
+  Hashed(HashResponse),
+  AudioChanged((bool, f64)),
+  VideoInteraction(bool),
+  MediaFailed(String),
+  ImageLoaded(String, u32, u32),
+  PipChanged(bool),
+  TransitionEnd,
+  SeedSet(ChangeData),
+  VisibilityChanged(bool),
+  ToolboxHover(bool),
+  Navigate(String),
+  PopState(String, String, String),
+  MultiPageLoaded(usize, Option<(Vec<RedditItem>, String)>),
+  MultiSaved((Option<String>, Multireddit)),
+  MultiDeleted(String),
+  PrefsChanged(Preferences),
+  PrefsOverridden(bool),
+  PrefsReset,
+  Synced(SyncMessage),
 }
 
-const TIMEOUT_KEY: &str = "TIMEOUT_KEY";
-const TIMEOUT_ENABLED_KEY: &str = "TIMEOUT_ENABLED_KEY";
 const LIMIT: usize = 50;
+const FAVORITES_PATH: &str = "/favorites";
+/// Natural sizes of the images hosts serve in place of removed ones.
+const PLACEHOLDER_SIZES: [(u32, u32); 1] = [
+  (161, 81), // i.imgur.com/removed.png
+];
+/// How long a post has to stay on screen before it counts as seen.
+const SEEN_AFTER: Duration = Duration::from_secs(3);
+/// Keep in sync with the animation durations in style.css.
+const TRANSITION_DURATION: Duration = Duration::from_millis(600);
+/// Random posts to keep loaded ahead of the current one.
+const RANDOM_AHEAD: usize = 3;
+/// Give up on `/random` after this many posts without anything to show.
+const RANDOM_MAX_MISSES: u32 = 10;
+/// Listing pages to go through looking for a linked post before giving up.
+const TARGET_MAX_PAGES: usize = 10;
+/// Listing routes kept in memory for back and forward.
+const ROUTE_CACHE_SIZE: usize = 8;
+/// Smallest page asked of each subreddit of an interleaved multi.
+const MULTI_MIN_LIMIT: usize = 10;
 
 impl Model {
-  pub fn get_items(&mut self) -> yew::services::fetch::FetchTask {
+  #[cfg(not(feature = "backend"))]
+  fn fetch_page(&self, path: &str, after: Option<&String>, limit: usize,
+                to_msg: impl Fn(Option<(Vec<RedditItem>, String)>) -> Msg + 'static) -> FetchTask {
+    let thresholds = self.prefs.thresholds.clone();
     let callback = self.link.callback(
-      move |response: Response<Json<Result<RedditListings, Error>>>| {
+      move |response: Response<Json<Result<RedditListingResponse, Error>>>| {
         let (meta, Json(data)) = response.into_parts();
         if meta.status.is_success() {
           match data {
-            Ok(data) => {
-              if let Some(data) = data.get_items() {
-                Msg::ItemsLoaded(data)
-              } else {
-                Msg::ItemsFailed
-              }
+            Ok(data) => to_msg(data.get_items(&thresholds, js_sys::Date::now() / 1000.0)),
+            Err(e) => {
+              error!("{}", e);
+              to_msg(None)
             }
+          }
+        } else {
+          to_msg(None)
+        }
+      },
+    );
+
+    let request_url = if self.random() {
+      format!("https://www.reddit.com/{}/random/.json", path)
+    } else if let Some(after) = after {
+      format!("https://www.reddit.com/{}/.json?{}limit={}&after={}", path, self.query_prefix(), limit, after)
+    } else {
+      format!("https://www.reddit.com/{}/.json?{}limit={}", path, self.query_prefix(), limit)
+    };
+
+    let request = Request::get(&request_url).body(Nothing).unwrap();
+    FetchService::fetch(request, callback).unwrap()
+  }
+
+  #[cfg(feature = "backend")]
+  fn fetch_page(&self, path: &str, after: Option<&String>, limit: usize,
+                to_msg: impl Fn(Option<(Vec<RedditItem>, String)>) -> Msg + 'static) -> FetchTask {
+    let callback = self.link.callback(
+      move |response: Response<Json<Result<RedditItemsPage, Error>>>| {
+        let (meta, Json(data)) = response.into_parts();
+        if meta.status.is_success() {
+          match data {
+            Ok(page) => to_msg(Some((page.items, page.after))),
             Err(e) => {
               error!("{}", e);
-              Msg::ItemsFailed
+              to_msg(None)
             }
           }
         } else {
-          Msg::ItemsFailed
+          to_msg(None)
         }
       },
     );
 
-    let request_url = if let Some(after) = &self.after {
-      format!("https://www.reddit.com/{}/.json?limit={}&after={}", &self.url, LIMIT, after)
+    // The backend applies the thresholds while converting the listing.
+    let thresholds = self.prefs.thresholds.to_query();
+    let request_url = if self.random() {
+      format!("/api{}/random?{}", path, thresholds)
+    } else if let Some(after) = after {
+      format!("/api{}?{}{}limit={}&after={}", path, self.query_prefix(), thresholds, limit, after)
     } else {
-      format!("https://www.reddit.com/{}/.json?limit={}", &self.url, LIMIT)
+      format!("/api{}?{}{}limit={}", path, self.query_prefix(), thresholds, limit)
     };
 
     let request = Request::get(&request_url).body(Nothing).unwrap();
     FetchService::fetch(request, callback).unwrap()
   }
 
+  /// Subreddits `/random` picks a post from, `None` unless playing in
+  /// random order on a route that has them: a subreddit, whatever its sort,
+  /// or a multi.
+  fn random_path(&self) -> Option<String> {
+    if self.prefs.order != PlaybackOrder::Random || self.source != Source::Reddit {
+      return None;
+    }
+    match self.multi() {
+      Some(multi) => Some(format!("/r/{}", multi.subreddits.join("+"))),
+      None => scope_of(&self.url).filter(|scope| scope.starts_with("r/")).map(|scope| format!("/{}", scope)),
+    }
+  }
+
+  /// Whether posts come one at a time from `/random`. Routes without a
+  /// subreddit, like the frontpage or a search, play in listing order.
+  fn random(&self) -> bool {
+    self.random_path().is_some()
+  }
+
+  pub fn get_items(&mut self) -> FetchTask {
+    let path = match (self.random_path(), self.multi()) {
+      (Some(path), _) => path,
+      (None, Some(multi)) => multi.combined_path(),
+      (None, None) => self.url.clone(),
+    };
+    self.fetch_page(&path, self.after.as_ref(), LIMIT, |page| match page {
+      // Nothing more to page in.
+      Some((pictures, after)) if pictures.is_empty() && after.is_empty() => Msg::ItemsFailed,
+      Some(page) => Msg::ItemsLoaded(page),
+      None => Msg::ItemsFailed,
+    })
+  }
+
+  /// Fetches the next page of every subreddit of an interleaved multi that
+  /// has one, see `Msg::MultiPageLoaded`.
+  fn get_multi_items(&mut self, multi: &Multireddit) {
+    let limit = (LIMIT / multi.subreddits.len()).max(MULTI_MIN_LIMIT);
+    self.multi_afters.resize(multi.subreddits.len(), None);
+    self.multi_answered = false;
+    self.multi_pages = self.multi_afters.iter()
+        .map(|after| if after.as_ref().is_some_and(|after| after.is_empty()) { Some(Vec::new()) } else { None })
+        .collect();
+    self.multi_fts = multi.subreddits.iter().enumerate()
+        .filter(|(index, _)| self.multi_pages[*index].is_none())
+        .map(|(index, subreddit)| {
+          self.fetch_page(&multi.subreddit_path(subreddit), self.multi_afters[index].as_ref(), limit,
+                          move |page| Msg::MultiPageLoaded(index, page))
+        })
+        .collect();
+    if self.multi_fts.is_empty() {
+      self.link.send_message(Msg::ItemsFailed);
+    }
+  }
+
+  /// Local multi playing, if any.
+  fn multi(&self) -> Option<&Multireddit> {
+    let name = self.url.strip_prefix(MULTI_PREFIX)?;
+    self.multis.iter().find(|multi| multi.name == name)
+  }
+
+  fn store_multis(&mut self) {
+    if let Some(storage) = &mut self.storage {
+      multis::store(storage, &self.multis);
+    }
+  }
+
+  /// Key of the current route's preference overrides.
+  fn scope(&self) -> Option<String> {
+    scope_of(&self.url)
+  }
+
+  fn store_settings(&mut self) {
+    if let Some(storage) = &mut self.storage {
+      self.settings.store(storage);
+    }
+  }
+
+  /// Replaces the preferences of one scope, see `Settings::set`, and sends
+  /// just that scope to the other tabs so edits of different scopes merge.
+  fn change_settings(&mut self, scope: Option<String>, prefs: Option<Preferences>) {
+    self.settings.set(scope.clone(), prefs.clone());
+    self.store_settings();
+    self.send_sync(SyncMessage::Prefs { scope, prefs });
+  }
+
+  fn send_sync(&self, message: SyncMessage) {
+    if let Some(sync) = &self.sync {
+      sync.send(&message);
+    }
+  }
+
+  fn store_history(&mut self) {
+    if let Some(storage) = &mut self.storage {
+      self.history.store(storage);
+    }
+  }
+
+  /// Replays a change made in another tab. Settings and history are saved
+  /// again so what's stored is the merge, not whichever tab wrote last;
+  /// favourites are stored by the tab that changed them.
+  fn apply_sync(&mut self, message: SyncMessage) {
+    match message {
+      SyncMessage::Prefs { scope, prefs } => {
+        self.settings.set(scope, prefs);
+        self.store_settings();
+        self.apply_prefs(self.settings.for_scope(self.scope().as_ref()).clone());
+      }
+      SyncMessage::Seen { names, hashes } => {
+        let mut changed = false;
+        for hash in hashes {
+          changed |= self.history.insert_hash(hash);
+        }
+        for name in names {
+          changed |= self.history.insert(name);
+        }
+        if changed {
+          self.store_history();
+        }
+      }
+      SyncMessage::HistoryCleared => self.history.clear(),
+      SyncMessage::FavoritesPut(favorites) => {
+        for favorite in favorites {
+          let media_url = favorite.item.media_url();
+          match self.favorites.iter_mut().find(|known| known.item.media_url() == media_url) {
+            Some(known) => *known = favorite,
+            None => self.favorites.push(favorite),
+          }
+        }
+        Favorite::sort(&mut self.favorites);
+        self.favorites_changed();
+      }
+      SyncMessage::FavoriteRemoved(media_url) => {
+        self.remove_favorite(media_url);
+        self.favorites_changed();
+      }
+    }
+  }
+
+  /// Saves edited preferences where they came from, the route's override if
+  /// it has one, and puts them in effect.
+  fn set_prefs(&mut self, prefs: Preferences) {
+    let scope = self.settings.edited_scope(self.scope());
+    self.change_settings(scope, Some(prefs.clone()));
+    self.apply_prefs(prefs);
+  }
+
+  /// Puts `prefs` in effect, catching up whatever depends on the ones that changed.
+  fn apply_prefs(&mut self, prefs: Preferences) {
+    let previous = std::mem::replace(&mut self.prefs, prefs);
+    if previous.dedup_crossposts != self.prefs.dedup_crossposts {
+      self.dedup.set_by_crosspost(self.prefs.dedup_crossposts);
+    }
+    if previous.repost_mode != self.prefs.repost_mode {
+      if self.prefs.repost_mode == RepostMode::Off {
+        self.hasher.take();
+        self.reposts.clear();
+      } else {
+        let items = self.items.clone();
+        self.request_hashes(&items);
+      }
+    }
+    if previous.order != self.prefs.order {
+      self.random_misses = 0;
+      self.shuffle = ShuffleOrder::new(self.shuffle.seed());
+    }
+    if previous.keyword_rules != self.prefs.keyword_rules {
+      self.keyword_filter = KeywordFilter::new(self.prefs.keyword_rules.clone());
+    }
+    if previous.content_filter != self.prefs.content_filter || previous.keyword_rules != self.prefs.keyword_rules {
+      self.filters_changed();
+    }
+    if previous.timeout != self.prefs.timeout || previous.timeout_enabled != self.prefs.timeout_enabled {
+      self.refresh_interval();
+    }
+    self.check_next_load();
+  }
+
+  /// Listing query ready to have more parameters appended.
+  fn query_prefix(&self) -> String {
+    let query = match self.multi() {
+      Some(multi) => multi.query(),
+      None => self.query.clone(),
+    };
+    if query.is_empty() {
+      query
+    } else {
+      format!("{}&", query)
+    }
+  }
+
+  fn route(&self) -> String {
+    if self.query.is_empty() {
+      self.url.clone()
+    } else {
+      format!("{}?{}", self.url, self.query)
+    }
+  }
+
+  /// Moves to another route in place, parking what the current one loaded.
+  fn switch_route(&mut self, url: String, query: String, hash: String) {
+    if self.source == Source::Reddit && !self.items.is_empty() {
+      let route = self.route();
+      let seed = self.shuffle.seed();
+      let state = RouteState {
+        items: std::mem::take(&mut self.items),
+        after: self.after.take(),
+        current_index: self.current_index,
+        dedup: self.dedup.clone(),
+        multi_afters: std::mem::take(&mut self.multi_afters),
+        shuffle: std::mem::replace(&mut self.shuffle, ShuffleOrder::new(seed)),
+      };
+      self.route_cache.retain(|(cached, _)| cached != &route);
+      self.route_cache.push((route, state));
+      if self.route_cache.len() > ROUTE_CACHE_SIZE {
+        self.route_cache.remove(0);
+      }
+    }
+
+    self.url = url;
+    self.query = query;
+    self.source = if self.url == FAVORITES_PATH { Source::Favorites } else { Source::Reddit };
+    self.ft.take();
+    self.multi_fts.clear();
+    self.loading = false;
+    self.failed = false;
+    self.random_misses = 0;
+    self.shuffle = ShuffleOrder::new(self.shuffle.seed());
+    self.target = parse_location_hash(&hash);
+    self.target_pages = 0;
+    self.location_hash = hash;
+
+    let route = self.route();
+    let cached = self.route_cache.iter().position(|(cached, _)| cached == &route);
+    let restored = match cached.map(|index| self.route_cache.remove(index).1) {
+      Some(state) => {
+        self.items = state.items;
+        self.after = state.after;
+        self.current_index = state.current_index;
+        self.dedup = state.dedup;
+        self.multi_afters = state.multi_afters;
+        self.shuffle = state.shuffle;
+        true
+      }
+      None => {
+        self.items = if self.source == Source::Favorites { self.favorite_items() } else { Vec::new() };
+        self.after = None;
+        self.multi_afters.clear();
+        self.current_index = 0;
+        self.dedup.clear();
+        self.callback_items.emit(());
+        false
+      }
+    };
+    // Subreddits can have their own preferences.
+    self.apply_prefs(self.settings.for_scope(self.scope().as_ref()).clone());
+    if restored && self.prefs.order == PlaybackOrder::Shuffle {
+      // Carry on from the restored item rather than start a fresh order over.
+      self.shuffle.extend_to(self.items.len());
+    }
+    self.refresh_interval();
+  }
+
   fn check_next_load(&self) {
-    if self.loading {
+    if self.loading || self.source != Source::Reddit {
       return;
     }
-    let pictures_left = self.items.len() - self.current_index as usize;
-    if LIMIT / 3 > pictures_left {
+    let pictures_left = self.items.len().saturating_sub(self.position().max(0) as usize);
+    let ahead = if self.random() { RANDOM_AHEAD } else { LIMIT / 3 };
+    if ahead > pictures_left {
       self.callback_items.emit(());
     }
   }
 
+  /// Why auto next is on hold, if it is.
+  fn pause_reason(&self) -> Option<&'static str> {
+    if self.page_hidden {
+      Some("tab hidden")
+    } else if self.video_interacting {
+      Some("video in use")
+    } else if self.toolbox_hover {
+      Some("toolbox in use")
+    } else {
+      None
+    }
+  }
+
   fn refresh_interval(&mut self) {
     self.job.take();
-    if self.timeout_enable {
-      let handle = IntervalService::spawn(Duration::from_secs(self.timeout), self.callback_tick.clone());
+    if self.prefs.timeout_enabled && self.pause_reason().is_none() {
+      let handle = IntervalService::spawn(Duration::from_secs(self.prefs.timeout), self.callback_tick.clone());
       self.job = Some(Box::new(handle));
     }
   }
 
+  fn source_name(&self) -> &str {
+    match &self.source {
+      Source::Reddit => &self.url,
+      Source::Playlist(name) => name,
+      Source::Favorites => "favorites",
+    }
+  }
+
+  /// Favourites are matched by media url rather than by whole item, items
+  /// saved before pictures carried their size don't equal the same picture
+  /// loaded now.
+  fn is_favorite(&self, item: &RedditItem) -> bool {
+    self.favorites.iter().any(|favorite| favorite.item.media_url() == item.media_url())
+  }
+
+  fn favorite_items(&self) -> Vec<RedditItem> {
+    self.favorites.iter().map(|favorite| favorite.item.clone()).collect()
+  }
+
+  /// Saves `favorites` in this tab's database and sends them to the others.
+  fn put_favorites(&self, favorites: Vec<Favorite>) {
+    if let Some(db) = &self.favorites_db {
+      for favorite in &favorites {
+        if let Err(e) = db.put(favorite) {
+          error!("{:?}", e);
+        }
+      }
+    }
+    self.send_sync(SyncMessage::FavoritesPut(favorites));
+  }
+
+  /// Drops a favourite removed here or in another tab, remembering it until
+  /// the stored favourites have loaded.
+  fn remove_favorite(&mut self, media_url: String) {
+    self.favorites.retain(|favorite| favorite.item.media_url() != media_url);
+    if let Some(removed) = &mut self.favorites_removed {
+      removed.push(media_url);
+    }
+  }
+
+  /// Mirrors a change of `favorites` into `items` when playing them.
+  fn favorites_changed(&mut self) {
+    if self.source == Source::Favorites {
+      self.items = self.favorite_items();
+      self.check_bounds();
+    }
+  }
+
+  /// Restarts the seen timer whenever a different post comes on screen.
+  fn track_shown(&mut self) {
+    if let Some(item) = self.items.get(self.current_index as usize) {
+      if let RedditItemType::Video { .. } = item.item {
+        self.last_video = Some(item.video_sources());
+      }
+    }
+    let name = self.items.get(self.current_index as usize).map(|item| item.name.clone());
+    if name != self.shown_name {
+      self.seen_job = name.clone()
+          .filter(|name| !name.is_empty())
+          .map(|name| TimeoutService::spawn(SEEN_AFTER, self.link.callback(move |_| Msg::MarkSeen(name.clone()))));
+      self.shown_name = name;
+    }
+  }
+
+  /// Starts a transition whenever a different item comes on screen.
+  fn track_transition(&mut self) {
+    let current = self.items.get(self.current_index as usize);
+    if current.map(|item| item.media_url()) == self.shown.as_ref().map(|(_, item)| item.media_url()) {
+      return;
+    }
+    let current = current.map(|item| (self.current_index, item.clone()));
+    let previous = std::mem::replace(&mut self.shown, current);
+    self.outgoing = None;
+    self.transition_job = None;
+    if self.prefs.transition == Transition::Cut || self.shown.is_none() || prefers_reduced_motion() {
+      return;
+    }
+    if let Some((index, item)) = previous {
+      self.outgoing = Some((item, self.current_index >= index));
+      self.transition_job = Some(TimeoutService::spawn(TRANSITION_DURATION, self.link.callback(|_| Msg::TransitionEnd)));
+    }
+  }
+
+  /// Jumps to the post linked in the url once it is loaded.
+  fn seek_target(&mut self) {
+    let (name, sub_index) = match &self.target {
+      Some(target) => target.clone(),
+      None => return,
+    };
+    match self.items.iter().position(|item| item.name == name) {
+      Some(first) => {
+        let index = first + sub_index;
+        let same_post = self.items.get(index).is_some_and(|item| item.name == name);
+        self.current_index = if same_post { index } else { first } as i32;
+        self.target = None;
+      }
+      // Only listings page in more items to look through.
+      None if self.source != Source::Reddit && !self.items.is_empty() => {
+        warn!("linked post {} not found", name);
+        self.target = None;
+      }
+      None => {}
+    }
+  }
+
+  /// Mirrors the current post into the url hash so reloading or sharing comes back to it.
+  fn update_location(&mut self) {
+    if self.target.is_some() {
+      return;
+    }
+    let index = self.current_index.max(0) as usize;
+    let name = match self.items.get(index) {
+      Some(item) if !item.name.is_empty() => &item.name,
+      _ => return,
+    };
+    let sub_index = self.items[..index].iter().rev().take_while(|item| &item.name == name).count();
+    let hash = if sub_index == 0 { format!("#{}", name) } else { format!("#{}/{}", name, sub_index) };
+    if hash == self.location_hash {
+      return;
+    }
+    if let Some(history) = web_sys::window().and_then(|window| window.history().ok()) {
+      // The first post of a route takes over the route's own entry.
+      let result = if self.prefs.item_history && !self.location_hash.is_empty() {
+        history.push_state_with_url(&JsValue::NULL, "", Some(&hash))
+      } else {
+        history.replace_state_with_url(&JsValue::NULL, "", Some(&hash))
+      };
+      if let Err(e) = result {
+        error!("{:?}", e);
+      }
+    }
+    self.location_hash = hash;
+  }
+
+  fn passes_filters(&self, item: &RedditItem, screen_aspect: f64) -> bool {
+    self.prefs.content_filter.matches(item, screen_aspect) && self.keyword_filter.matches(item)
+  }
+
+  /// Filters apply to what listings load, and only to upcoming items so the
+  /// one on screen stays until moving on.
+  fn filters_changed(&mut self) {
+    if self.source != Source::Reddit {
+      return;
+    }
+    let screen_aspect = screen_aspect();
+    let filtered: Vec<usize> = self.upcoming().into_iter()
+        .filter(|index| !self.passes_filters(&self.items[*index], screen_aspect))
+        .collect();
+    self.remove_items(&filtered);
+    self.check_next_load();
+  }
+
+  /// Indices of the items still ahead in playback order, ascending.
+  fn upcoming(&self) -> Vec<usize> {
+    let current = self.position();
+    (0..self.items.len())
+        .filter(|index| match self.prefs.order {
+          // Items loaded since the order was last extended haven't played yet.
+          PlaybackOrder::Shuffle => self.shuffle.position(*index).is_none_or(|position| position as i32 > current),
+          _ => *index as i32 > current,
+        })
+        .collect()
+  }
+
+  /// Removes the items at ascending `indices`, keeping the current item and
+  /// the shuffle order pointing at the same items.
+  fn remove_items(&mut self, indices: &[usize]) {
+    for index in indices.iter().rev() {
+      self.items.remove(*index);
+      self.shuffle.remove(*index);
+      if (*index as i32) < self.current_index {
+        self.current_index -= 1;
+      }
+    }
+  }
+
+  /// Appends a freshly loaded page, minus duplicates and seen posts.
+  fn add_items(&mut self, pictures: Vec<RedditItem>) {
+    let (mut pictures, duplicates) = self.dedup.filter(pictures);
+    for duplicate in duplicates {
+      info!("skipped duplicate {} {}", duplicate.name, duplicate.media_url());
+    }
+    let loaded = pictures.len();
+    let screen_aspect = screen_aspect();
+    pictures.retain(|item| self.passes_filters(item, screen_aspect));
+    if pictures.len() < loaded {
+      info!("filtered out {} items", loaded - pictures.len());
+    }
+    if self.prefs.skip_seen {
+      let loaded = pictures.len();
+      let target = self.target.as_ref().map(|(name, _)| name);
+      pictures.retain(|item| !self.history.contains(&item.name) || Some(&item.name) == target);
+      if pictures.len() < loaded {
+        info!("skipped {} already seen items", loaded - pictures.len());
+      }
+    }
+    self.request_hashes(&pictures);
+    if self.random() {
+      self.random_misses = if pictures.is_empty() { self.random_misses + 1 } else { 0 };
+      if self.random_misses >= RANDOM_MAX_MISSES {
+        warn!("{} random posts in a row had nothing to show", self.random_misses);
+        self.random_misses = 0;
+        self.failed = true;
+        return;
+      }
+    }
+    self.items.extend(pictures);
+    if let Some((name, _)) = &self.target {
+      if !self.items.iter().any(|item| &item.name == name) {
+        self.target_pages += 1;
+        if self.target_pages >= TARGET_MAX_PAGES || self.random() {
+          warn!("linked post {} not found", name);
+          self.target = None;
+        } else {
+          self.callback_items.emit(());
+        }
+      }
+    }
+    self.check_next_load();
+  }
+
+  /// Sends not yet hashed pictures to the hash worker, spawning it on first use.
+  fn request_hashes(&mut self, items: &[RedditItem]) {
+    if self.prefs.repost_mode == RepostMode::Off {
+      return;
+    }
+    let link = &self.link;
+    let hasher = self.hasher.get_or_insert_with(|| HashWorker::bridge(link.callback(Msg::Hashed)));
+    for item in items {
+      if let Some(thumbnail_url) = item.thumbnail_url() {
+        if !self.hashes.contains_key(item.media_url()) {
+          hasher.send(HashRequest { key: item.media_url().to_string(), thumbnail_url: thumbnail_url.to_string() });
+        }
+      }
+    }
+  }
+
+  fn is_broken(&self, index: i32) -> bool {
+    self.items.get(index as usize).is_some_and(|item| self.broken.contains(item.media_url()))
+  }
+
+  /// Position of the current item in playback order.
+  fn position(&self) -> i32 {
+    match self.prefs.order {
+      PlaybackOrder::Shuffle => self.shuffle.position(self.current_index.max(0) as usize).map_or(0, |position| position as i32),
+      _ => self.current_index,
+    }
+  }
+
+  /// Index of the item played at `position`, out of range positions map to
+  /// out of range indices.
+  fn index_at(&self, position: i32) -> i32 {
+    match self.prefs.order {
+      PlaybackOrder::Shuffle if position >= 0 => self.shuffle.get(position as usize).map_or(self.items.len() as i32, |index| index as i32),
+      _ => position,
+    }
+  }
+
+  /// Moves `delta` items away in playback order, passing over broken ones.
+  /// Stays put when there is nothing but broken items that way, callers
+  /// then page in more with `check_next_load`.
+  fn step(&mut self, delta: i32) {
+    let positions = 0..self.items.len() as i32;
+    let mut position = (self.position() + delta).min(positions.end - 1).max(0);
+    while positions.contains(&position) && self.is_broken(self.index_at(position)) {
+      position += delta.signum();
+    }
+    if positions.contains(&position) {
+      self.current_index = self.index_at(position);
+    }
+    self.check_bounds();
+  }
+
   fn check_bounds(&mut self) {
     if self.current_index < 0 {
       self.current_index = 0;
@@ -120,33 +879,54 @@ impl Component for Model {
   fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
     let window: web_sys::Window = web_sys::window().expect("window not available");
     let location = window.location();
-    let mut timeout = 10;
-    let mut timeout_enabled = true;
-    let handle = IntervalService::spawn(Duration::from_secs(timeout), link.callback(|_| Msg::Tick));
+    let url = location.pathname().unwrap();
+    let mut storage = StorageService::new(Area::Local).ok();
+    let settings = storage.as_mut().map(Settings::restore).unwrap_or_default();
+    let prefs = settings.for_scope(scope_of(&url).as_ref()).clone();
+    let handle = IntervalService::spawn(Duration::from_secs(prefs.timeout), link.callback(|_| Msg::Tick));
     link.callback(|_| Msg::LoadItems).emit(());
     let initial_vec = Vec::new();
-    let storage = StorageService::new(Area::Local).ok();
-    if let Some(storage) = &storage {
-      if let Ok(timeout_enabled_val) = storage.restore(TIMEOUT_ENABLED_KEY) {
-        if let Ok(timeout_enabled_val) = timeout_enabled_val.parse::<bool>() {
-          timeout_enabled = timeout_enabled_val;
-        }
-      }
-
-      if let Ok(timeout_val) = storage.restore(TIMEOUT_KEY) {
-        if let Ok(timeout_val) = timeout_val.parse::<u64>() {
-          timeout = timeout_val;
-        }
+    if let Err(e) = FavoritesDb::open(link.callback(Msg::FavoritesOpened)) {
+      error!("{:?}", e);
+    }
+    let location_hash = location.hash().unwrap_or_default();
+    let query = location.search().unwrap_or_default().trim_start_matches('?').to_string();
+    let source = if url == FAVORITES_PATH { Source::Favorites } else { Source::Reddit };
+    let document = window.document().expect("document not available");
+    let visibility_link = link.clone();
+    let visibility_listener = Closure::wrap(Box::new(move |_: Event| {
+      let hidden = web_sys::window().and_then(|window| window.document()).is_some_and(|document| document.hidden());
+      visibility_link.send_message(Msg::VisibilityChanged(hidden));
+    }) as Box<dyn Fn(Event)>);
+    if let Err(e) = document.add_event_listener_with_callback("visibilitychange", visibility_listener.as_ref().unchecked_ref()) {
+      error!("{:?}", e);
+    }
+    let popstate_link = link.clone();
+    let popstate_listener = Closure::wrap(Box::new(move |_: Event| {
+      if let Some(location) = web_sys::window().map(|window| window.location()) {
+        popstate_link.send_message(Msg::PopState(
+          location.pathname().unwrap_or_default(),
+          location.search().unwrap_or_default().trim_start_matches('?').to_string(),
+          location.hash().unwrap_or_default(),
+        ));
       }
+    }) as Box<dyn Fn(Event)>);
+    if let Err(e) = window.add_event_listener_with_callback("popstate", popstate_listener.as_ref().unchecked_ref()) {
+      error!("{:?}", e);
+    }
+    let sync = TabSync::open(link.callback(Msg::Synced)).map_err(|e| error!("{:?}", e)).ok();
+    let mut history = SeenHistory::default();
+    let mut multis = Vec::new();
+    if let Some(storage) = &storage {
+      history = SeenHistory::restore(storage);
+      multis = multis::restore(storage);
     }
 
     Self {
-      timeout,
-      timeout_enable: timeout_enabled,
       items: initial_vec,
       current_index: 0,
-      url: location.pathname().unwrap(),
-      job: Some(Box::new(handle)),
+      url,
+      job: if document.hidden() { None } else { Some(Box::new(handle)) },
       callback_tick: link.callback(|_| Msg::Tick),
       callback_items: link.callback(|_| Msg::LoadItems),
       link,
@@ -155,40 +935,57 @@ impl Component for Model {
       after: None,
       ft: None,
       storage,
+      source,
+      reader_task: None,
+      favorites: Vec::new(),
+      favorites_db: None,
+      favorites_removed: Some(Vec::new()),
+      history,
+      shown_name: None,
+      seen_job: None,
+      dedup: Deduplicator::new(prefs.dedup_crossposts),
+      hasher: None,
+      hashes: HashMap::new(),
+      reposts: HashSet::new(),
+      video_interacting: false,
+      broken: HashSet::new(),
+      pip: false,
+      last_video: None,
+      shown: None,
+      outgoing: None,
+      transition_job: None,
+      shuffle: ShuffleOrder::new(random_seed()),
+      random_misses: 0,
+      page_hidden: document.hidden(),
+      toolbox_hover: false,
+      _visibility_listener: visibility_listener,
+      target: parse_location_hash(&location_hash),
+      target_pages: 0,
+      location_hash,
+      query,
+      route_cache: Vec::new(),
+      multis,
+      multi_afters: Vec::new(),
+      multi_pages: Vec::new(),
+      multi_fts: Vec::new(),
+      multi_answered: false,
+      keyword_filter: KeywordFilter::new(prefs.keyword_rules.clone()),
+      settings,
+      prefs,
+      _popstate_listener: popstate_listener,
+      sync,
     }
   }
 
   fn update(&mut self, msg: Self::Message) -> ShouldRender {
     match msg {
-      Msg::TimeoutToggle => {
-        self.timeout_enable = !self.timeout_enable;
-        if let Some(storage) = &mut self.storage {
-          storage.store(TIMEOUT_ENABLED_KEY, Ok(self.timeout_enable.to_string()));
-        }
-        self.refresh_interval();
-      }
-      Msg::TimeoutSet(data) => {
-        if let ChangeData::Value(timeout_str) = data {
-          if let Ok(timeout) = timeout_str.parse::<u64>() {
-            if timeout != self.timeout && timeout > 0 {
-              self.timeout = timeout;
-              if let Some(storage) = &mut self.storage {
-                storage.store(TIMEOUT_KEY, Ok(self.timeout.to_string()));
-              }
-              self.refresh_interval();
-            }
-          }
-        }
-      }
       Msg::NextPicture => {
-        self.current_index += 1;
-        self.check_bounds();
+        self.step(1);
         self.check_next_load();
         self.refresh_interval();
       }
       Msg::PrevPicture => {
-        self.current_index -= 1;
-        self.check_bounds();
+        self.step(-1);
         self.check_next_load();
         self.refresh_interval();
       }
@@ -199,30 +996,392 @@ impl Component for Model {
         self.refresh_interval();
       }
       Msg::Tick => {
-        if !self.timeout_enable {
+        if !self.prefs.timeout_enabled {
           return false;
         }
-        self.current_index += 1;
-        self.check_bounds();
+        self.step(1);
         self.check_next_load();
       }
       Msg::ItemsLoaded((pictures, after)) => {
         self.loading = false;
-        self.after = Some(after);
-        self.items.extend(pictures);
+        // A random post isn't a page of the listing, keep paging where it was.
+        if !self.random() {
+          self.after = Some(after);
+        }
+        self.add_items(pictures);
+      }
+      Msg::MultiPageLoaded(index, page) => {
+        // A failed subreddit keeps its cursor to be tried again with the next load.
+        let pictures = match page {
+          Some((pictures, after)) => {
+            if let Some(cursor) = self.multi_afters.get_mut(index) {
+              *cursor = Some(after);
+            }
+            self.multi_answered = true;
+            pictures
+          }
+          None => {
+            warn!("failed to load subreddit {} of {}", index, self.url);
+            Vec::new()
+          }
+        };
+        if let Some(slot) = self.multi_pages.get_mut(index) {
+          *slot = Some(pictures);
+        }
+        if self.multi_pages.iter().any(Option::is_none) {
+          return false;
+        }
+        self.multi_fts.clear();
+        self.loading = false;
+        // Take turns, one post per subreddit, so gallery items stay together.
+        let mut pages: Vec<std::collections::VecDeque<RedditItem>> = self.multi_pages.drain(..)
+            .map(|page| page.unwrap_or_default().into_iter().collect())
+            .collect();
+        let mut pictures = Vec::new();
+        while pages.iter().any(|page| !page.is_empty()) {
+          for page in pages.iter_mut() {
+            if let Some(first) = page.pop_front() {
+              let name = first.name.clone();
+              pictures.push(first);
+              while page.front().is_some_and(|item| !name.is_empty() && item.name == name) {
+                pictures.extend(page.pop_front());
+              }
+            }
+          }
+        }
+        let exhausted = self.multi_afters.iter().all(|after| after.as_ref().is_some_and(|after| after.is_empty()));
+        if pictures.is_empty() && (exhausted || !self.multi_answered) {
+          self.failed = true;
+          return true;
+        }
+        self.add_items(pictures);
+      }
+      Msg::PrefsChanged(prefs) => self.set_prefs(prefs),
+      Msg::PrefsOverridden(overridden) => {
+        let scope = match self.scope() {
+          Some(scope) => scope,
+          None => return false,
+        };
+        self.change_settings(Some(scope), if overridden { Some(self.prefs.clone()) } else { None });
+        self.apply_prefs(self.settings.for_scope(self.scope().as_ref()).clone());
+      }
+      Msg::PrefsReset => self.set_prefs(Preferences::default()),
+      Msg::MultiSaved((original, multi)) => {
+        let renamed = original.filter(|original| *original != multi.name);
+        // A multi's filters are its own preferences, created and renamed along with it.
+        let original_scope = renamed.as_ref().and_then(|original| scope_of(&multis::route(original)));
+        let moved = original_scope.as_ref().and_then(|scope| self.settings.overrides.get(scope).cloned());
+        if moved.is_some() {
+          self.change_settings(original_scope, None);
+        }
+        if let Some(scope) = scope_of(&multi.route()) {
+          let prefs = match moved {
+            Some(prefs) => Some(prefs),
+            None if !self.settings.overrides.contains_key(&scope) => Some(self.settings.base.clone()),
+            None => None,
+          };
+          if prefs.is_some() {
+            self.change_settings(Some(scope), prefs);
+          }
+        }
+        let playing_renamed = renamed.as_ref().is_some_and(|original| self.url == multis::route(original));
+        if let Some(original) = &renamed {
+          self.multis.retain(|saved| saved.name != *original);
+        }
+        let route = multi.route();
+        match self.multis.iter_mut().find(|saved| saved.name == multi.name) {
+          Some(saved) => *saved = multi,
+          None => self.multis.push(multi),
+        }
+        self.store_multis();
+        if playing_renamed {
+          self.link.send_message(Msg::Navigate(route));
+        } else {
+          self.apply_prefs(self.settings.for_scope(self.scope().as_ref()).clone());
+        }
+      }
+      Msg::MultiDeleted(name) => {
+        self.multis.retain(|multi| multi.name != name);
+        self.store_multis();
+        if let Some(scope) = scope_of(&multis::route(&name)) {
+          if self.settings.overrides.contains_key(&scope) {
+            self.change_settings(Some(scope), None);
+            self.apply_prefs(self.settings.for_scope(self.scope().as_ref()).clone());
+          }
+        }
       }
       Msg::ItemsFailed => {
         self.loading = false;
         self.failed = true;
       }
       Msg::LoadItems => {
-        if !self.loading {
+        if !self.loading && self.source == Source::Reddit {
           self.loading = true;
           self.ft.take();
-          self.ft = Some(self.get_items());
+          match self.multi().filter(|multi| multi.interleave && !self.random()).cloned() {
+            Some(multi) => self.get_multi_items(&multi),
+            None => self.ft = Some(self.get_items()),
+          }
         }
       }
+      Msg::ExportPlaylist => {
+        let name = self.source_name().to_string();
+        let playlist = Playlist::new(name.clone(), self.items.clone());
+        match playlist.to_json() {
+          Ok(json) => {
+            if let Err(e) = playlist::download_json(&format!("{}.json", playlist::file_stem(&name)), &json) {
+              error!("{:?}", e);
+            }
+          }
+          Err(e) => error!("{}", e),
+        }
+        return false;
+      }
+      Msg::ImportPlaylist(data) => {
+        if let ChangeData::Files(files) = data {
+          if let Some(file) = files.get(0) {
+            let callback = self.link.callback(Msg::PlaylistRead);
+            match ReaderService::new().read_file(file, callback) {
+              Ok(task) => self.reader_task = Some(task),
+              Err(e) => error!("{}", e),
+            }
+          }
+        }
+        return false;
+      }
+      Msg::PlaylistRead(file) => {
+        self.reader_task.take();
+        let playlist = std::str::from_utf8(&file.content)
+            .map_err(|e| e.to_string())
+            .and_then(|json| Playlist::from_json(json).map_err(|e| e.to_string()));
+        match playlist {
+          Ok(playlist) => {
+            self.ft.take();
+            self.multi_fts.clear();
+            self.multi_pages.clear();
+            self.loading = false;
+            self.failed = false;
+            self.after = None;
+            self.multi_afters.clear();
+            self.random_misses = 0;
+            self.shuffle = ShuffleOrder::new(self.shuffle.seed());
+            self.dedup.clear();
+            self.target = None;
+            self.items = playlist.items;
+            self.current_index = 0;
+            self.source = Source::Playlist(playlist.name);
+            self.refresh_interval();
+          }
+          Err(e) => error!("{}: {}", file.name, e),
+        }
+      }
+      Msg::FavoritesOpened(db) => {
+        match db {
+          Ok(db) => {
+            if let Err(e) = db.load(self.link.callback(Msg::FavoritesLoaded)) {
+              error!("{:?}", e);
+            }
+            self.favorites_db = Some(db);
+          }
+          Err(e) => error!("{:?}", e),
+        }
+        return false;
+      }
+      Msg::FavoritesLoaded(mut favorites) => {
+        // Favourites removed, here or in another tab, while these were loading
+        // are deleted; the ones added go after the stored ones.
+        let removed = self.favorites_removed.take().unwrap_or_default();
+        favorites.retain(|favorite| !removed.iter().any(|media_url| media_url == favorite.item.media_url()));
+        if let Some(db) = &self.favorites_db {
+          for media_url in &removed {
+            if let Err(e) = db.delete(media_url) {
+              error!("{:?}", e);
+            }
+          }
+        }
+        let pending = std::mem::replace(&mut self.favorites, favorites);
+        let mut added = Vec::new();
+        for mut favorite in pending {
+          if !self.is_favorite(&favorite.item) {
+            favorite.position = Favorite::position_between(self.favorites.last(), None);
+            self.favorites.push(favorite.clone());
+            added.push(favorite);
+          }
+        }
+        if !added.is_empty() {
+          self.put_favorites(added);
+        }
+        self.favorites_changed();
+      }
+      Msg::ToggleFavorite => {
+        if let Some(item) = self.items.get(self.current_index as usize).cloned() {
+          if self.is_favorite(&item) {
+            self.remove_favorite(item.media_url().to_string());
+            if let Some(db) = &self.favorites_db {
+              if let Err(e) = db.delete(item.media_url()) {
+                error!("{:?}", e);
+              }
+            }
+            self.send_sync(SyncMessage::FavoriteRemoved(item.media_url().to_string()));
+          } else {
+            let favorite = Favorite { position: Favorite::position_between(self.favorites.last(), None), item };
+            self.favorites.push(favorite.clone());
+            self.put_favorites(vec![favorite]);
+          }
+          self.favorites_changed();
+        }
+      }
+      Msg::MoveFavorite(delta) => {
+        let from = self.current_index;
+        let to = from + delta;
+        if self.source != Source::Favorites || from < 0 || to < 0 || to as usize >= self.favorites.len() {
+          return false;
+        }
+        let (from, to) = (from as usize, to as usize);
+        self.favorites.swap(from, to);
+        // Only the moved favourite changes, it takes a position between its
+        // new neighbours.
+        let before = to.checked_sub(1).and_then(|index| self.favorites.get(index));
+        let position = Favorite::position_between(before, self.favorites.get(to + 1));
+        self.favorites[to].position = position;
+        self.put_favorites(vec![self.favorites[to].clone()]);
+        self.current_index = to as i32;
+        self.favorites_changed();
+      }
+      Msg::MarkSeen(name) => {
+        self.seen_job.take();
+        let mut hashes = Vec::new();
+        for item in self.items.iter().filter(|item| item.name == name) {
+          if let Some(hash) = self.hashes.get(item.media_url()) {
+            if self.history.insert_hash(*hash) {
+              hashes.push(*hash);
+            }
+          }
+        }
+        let names = if self.history.insert(name.clone()) { vec![name] } else { Vec::new() };
+        if names.is_empty() && hashes.is_empty() {
+          return false;
+        }
+        self.store_history();
+        self.send_sync(SyncMessage::Seen { names, hashes });
+      }
+      Msg::Hashed(HashResponse { key, hash }) => {
+        let hash = match hash {
+          Some(hash) => hash,
+          None => return false,
+        };
+        self.hashes.insert(key.clone(), hash);
+        match self.history.closest_hash(hash) {
+          Some(distance) if distance <= self.prefs.repost_distance => {}
+          _ => return false,
+        }
+        match self.prefs.repost_mode {
+          RepostMode::Off => return false,
+          RepostMode::Flag => {
+            self.reposts.insert(key);
+          }
+          RepostMode::Hide => {
+            // Only drop upcoming items, never the one on screen or already passed.
+            let reposts: Vec<usize> = self.upcoming().into_iter()
+                .filter(|index| self.items[*index].media_url() == key)
+                .collect();
+            if reposts.is_empty() {
+              return false;
+            }
+            self.remove_items(&reposts);
+            info!("hid repost {}", key);
+          }
+        }
+      }
+      Msg::AudioChanged((muted, volume)) => {
+        let mut prefs = self.prefs.clone();
+        prefs.sound_on = !muted;
+        prefs.volume = volume;
+        self.set_prefs(prefs);
+      }
+      Msg::VideoInteraction(interacting) => {
+        self.video_interacting = interacting;
+        self.refresh_interval();
+      }
+      Msg::VisibilityChanged(hidden) => {
+        self.page_hidden = hidden;
+        self.refresh_interval();
+      }
+      Msg::Navigate(route) => {
+        let (url, query) = match route.split_once('?') {
+          Some((url, query)) => (url.to_string(), query.to_string()),
+          None => (route.clone(), String::new()),
+        };
+        if let Some(history) = web_sys::window().and_then(|window| window.history().ok()) {
+          if let Err(e) = history.push_state_with_url(&JsValue::NULL, "", Some(&route)) {
+            error!("{:?}", e);
+          }
+        }
+        self.switch_route(url, query, String::new());
+      }
+      Msg::PopState(url, query, hash) => {
+        if url == self.url && query == self.query {
+          self.target = parse_location_hash(&hash);
+          self.target_pages = 0;
+          self.location_hash = hash;
+        } else {
+          self.switch_route(url, query, hash);
+        }
+      }
+      Msg::ToolboxHover(hover) => {
+        self.toolbox_hover = hover;
+        self.refresh_interval();
+      }
+      Msg::PipChanged(pip) => {
+        self.pip = pip;
+      }
+      Msg::TransitionEnd => {
+        self.transition_job.take();
+        self.outgoing.take();
+      }
+      Msg::SeedSet(data) => {
+        if let ChangeData::Value(seed_str) = data {
+          if let Ok(seed) = seed_str.parse::<u64>() {
+            self.shuffle = ShuffleOrder::new(seed);
+          }
+        }
+      }
+      Msg::ImageLoaded(url, width, height) => {
+        if !PLACEHOLDER_SIZES.contains(&(width, height)) {
+          return false;
+        }
+        self.link.send_message(Msg::MediaFailed(url));
+        return false;
+      }
+      Msg::MediaFailed(url) => {
+        warn!("failed to load {}", url);
+        self.broken.insert(url.clone());
+        let current = self.items.get(self.current_index as usize).map(|item| item.media_url() == url);
+        if current == Some(true) {
+          self.step(1);
+          self.check_next_load();
+          self.refresh_interval();
+        }
+      }
+      Msg::ClearHistory => {
+        self.history.clear();
+        self.store_history();
+        self.send_sync(SyncMessage::HistoryCleared);
+      }
+      Msg::Synced(message) => self.apply_sync(message),
     }
+    if self.prefs.order == PlaybackOrder::Shuffle {
+      // A fresh order starts the session over from its first item.
+      let started = self.shuffle.is_empty();
+      self.shuffle.extend_to(self.items.len());
+      if started && !self.shuffle.is_empty() {
+        self.current_index = self.index_at(0);
+      }
+    }
+    self.seek_target();
+    self.track_shown();
+    self.track_transition();
+    self.update_location();
     true
   }
 
@@ -231,28 +1390,57 @@ impl Component for Model {
   }
 
   fn view(&self) -> Html {
+    let view_title = |item: &RedditItem| {
+      html! {
+        <div id="main-title">
+          <a class={ if self.is_favorite(item) { "favorite-toggle favorite" } else { "favorite-toggle" } }
+             title="Favorite" onclick=self.link.callback(|_| Msg::ToggleFavorite)>{ "\u{2605}" }</a>
+          {
+            if self.reposts.contains(item.media_url()) {
+              html! { <span class="repost-flag">{"repost"}</span> }
+            } else {
+              html! { <></> }
+            }
+          }
+          <a target="_blank" href=format!("{}", &item.title_url)>{ &item.title }</a>
+        </div>
+      }
+    };
+
+    let reduced_motion = prefers_reduced_motion();
     let view_item = |item: &RedditItem| {
       match &item.item {
-        RedditItemType::Picture { source_set, url } => {
+        RedditItemType::Picture { source_set, url, .. } => {
+          let load_url = url.clone();
+          let error_url = url.clone();
+          // Pick the pan direction from the url so it stays put while the item is on screen.
+          let (class, style) = if self.prefs.ken_burns && !reduced_motion {
+            let variant = url.bytes().fold(0u32, |sum, b| sum.wrapping_add(b as u32)) % 4;
+            (format!("ken-burns ken-burns-{}", variant), format!("animation-duration: {}s", self.prefs.timeout))
+          } else {
+            (String::new(), String::new())
+          };
           html! {
               <>
-                <div id="main-title"><a target="_blank" href=format!("{}", &item.title_url)>{ &item.title }</a></div>
-                <img id="main-image" src={ url } srcset={ source_set } loading="lazy" sizes="100vw" />
-              </>
-          }
-        }
-        RedditItemType::Video { url, mime } => {
-          html! {
-              <>
-                <div id="main-title"><a target="_blank" href=format!("{}", &item.title_url)>{ &item.title }</a></div>
-                <Player id="main-video" url={ url } mime={ mime } />
+                { view_title(item) }
+                <img id="main-image" class=class style=style src={ url } srcset={ source_set } loading="lazy" sizes="100vw"
+                     onload=self.link.callback(move |event: Event| {
+                       let (width, height) = event.target()
+                           .and_then(|target| target.dyn_into::<HtmlImageElement>().ok())
+                           .map_or((0, 0), |image| (image.natural_width(), image.natural_height()));
+                       Msg::ImageLoaded(load_url.clone(), width, height)
+                     })
+                     onerror=self.link.callback(move |_| Msg::MediaFailed(error_url.clone())) />
               </>
           }
         }
+        // The player lives in its own slot below so that it survives
+        // pictures while it is in picture-in-picture.
+        RedditItemType::Video { .. } => view_title(item),
         RedditItemType::Embed { url, width, height, scrolling } => {
           html! {
               <>
-                <div id="main-title"><a target="_blank" href=format!("{}", &item.title_url)>{ &item.title }</a></div>
+                { view_title(item) }
                 <div id="main-iframe-center">
                   <iframe id="main-iframe" src={url} width={width} height={height} scrolling={scrolling} border=0 frameborder=0 allowfullscreen=true
                    sandbox="allow-forms allow-same-origin allow-scripts allow-top-navigation-by-user-activation allow-popups allow-popups-to-escape-sandbox allow-orientation-lock allow-presentation"></iframe>
@@ -263,15 +1451,45 @@ impl Component for Model {
       }
     };
 
+    // Slides are keyed by media url so the outgoing one keeps its element
+    // while the incoming one is mounted next to it.
+    let view_slide = |item: &RedditItem, class: String| {
+      html! {
+        <div key=item.media_url().to_string() class=class>{ view_item(item) }</div>
+      }
+    };
+
     let tool_box_number_view = |item: (usize, &RedditItem)| {
       let index = item.0 as i32;
-      if self.current_index == index {
-        html! { <li><a class="item-selected" onclick=self.link.callback(move |_| Msg::SetIndex(index))>{ index + 1 }</a></li> }
+      let class = if self.current_index == index {
+        "item-selected"
+      } else if self.broken.contains(item.1.media_url()) {
+        "item-broken"
       } else {
-        html! { <li><a onclick=self.link.callback(move |_| Msg::SetIndex(index))>{ index + 1 }</a></li> }
-      }
+        ""
+      };
+      html! { <li><a class=class onclick=self.link.callback(move |_| Msg::SetIndex(index))>{ index + 1 }</a></li> }
     };
     let item = self.items.get(self.current_index as usize);
+    let showing_video = matches!(item.map(|item| &item.item), Some(RedditItemType::Video { .. }));
+    let player_sources = if showing_video {
+      item.map(|item| item.video_sources())
+    } else if self.pip {
+      self.last_video.clone()
+    } else {
+      None
+    };
+    let direction = |forward: bool| if forward { "forward" } else { "backward" };
+    let outgoing = self.outgoing.iter()
+        .filter(|(outgoing, _)| item.map(|item| item.media_url()) != Some(outgoing.media_url()))
+        .map(|(outgoing, forward)| {
+          view_slide(outgoing, format!("slide slide-out transition-{} {}", self.prefs.transition.as_str(), direction(*forward)))
+        });
+    let incoming = item.map(|item| match &self.outgoing {
+      Some((_, forward)) => view_slide(item, format!("slide slide-in transition-{} {}", self.prefs.transition.as_str(), direction(*forward))),
+      None => view_slide(item, "slide".to_string()),
+    });
+    let slides = outgoing.chain(incoming);
     html! {
             <div id="main">
                 {
@@ -283,20 +1501,94 @@ impl Component for Model {
                     html!{ <></> }
                   }
                 }
+                { for slides }
                 {
-                  if let Some(item) = item.map(view_item) {
-                     item
-                  } else{
-                    html!{ <></> }
+                  if let Some(sources) = player_sources {
+                    html! {
+                      <Player id="main-video" sources=sources hidden={ !showing_video } muted={ !self.prefs.sound_on } volume={ self.prefs.volume }
+                              on_audio_change=self.link.callback(Msg::AudioChanged)
+                              on_interaction=self.link.callback(Msg::VideoInteraction)
+                              on_pip_change=self.link.callback(Msg::PipChanged)
+                              on_error=self.link.callback(Msg::MediaFailed) />
+                    }
+                  } else {
+                    html! { <></> }
                   }
                 }
                 <div class="prev-button" onclick=self.link.callback(|_| Msg::PrevPicture)></div>
                 <div class="next-button" onclick=self.link.callback(|_| Msg::NextPicture)></div>
-                <div class="toolbox">
+                {
+                  match self.pause_reason().filter(|_| self.prefs.timeout_enabled) {
+                    Some(reason) => html! { <div class="paused-indicator">{ format!("Paused: {}", reason) }</div> },
+                    None => html! { <></> },
+                  }
+                }
+                <div class="toolbox" onmouseenter=self.link.callback(|_| Msg::ToolboxHover(true))
+                     onmouseleave=self.link.callback(|_| Msg::ToolboxHover(false))>
                     <div class="toolbox-header">
-                      <strong class="reddit-name">{ &self.url }</strong><br/>
-                      <input type="checkbox" checked={self.timeout_enable} onchange=self.link.callback(|_| Msg::TimeoutToggle) /> <strong>{"Auto next"}</strong>{" every"}
-                      <input type="number" class="number-input" value={self.timeout} onchange=self.link.callback(|data| Msg::TimeoutSet(data)) /> {"seconds"}
+                      {
+                        if self.source == Source::Reddit {
+                          html! { <></> }
+                        } else {
+                          html! { <strong class="reddit-name">{ self.source_name() }</strong> }
+                        }
+                      }
+                      <Switcher route=self.route() on_switch=self.link.callback(Msg::Navigate) />
+                      <MultiEditor multis=self.multis.clone()
+                                   on_save=self.link.callback(Msg::MultiSaved)
+                                   on_delete=self.link.callback(Msg::MultiDeleted)
+                                   on_open=self.link.callback(Msg::Navigate) />
+                      <a class="toolbox-action" href="javascript:void(0)" onclick=self.link.callback(|_| Msg::ExportPlaylist)>{"Export"}</a>
+                      <label class="toolbox-action">{"Import"}
+                        <input type="file" accept=".json,application/json" onchange=self.link.callback(Msg::ImportPlaylist) />
+                      </label>
+                      {
+                        if self.source == Source::Favorites {
+                          html! {
+                            <>
+                              <a class="toolbox-action" href="javascript:void(0)" onclick=self.link.callback(|_| Msg::MoveFavorite(-1))>{"Move earlier"}</a>
+                              <a class="toolbox-action" href="javascript:void(0)" onclick=self.link.callback(|_| Msg::MoveFavorite(1))>{"Move later"}</a>
+                            </>
+                          }
+                        } else {
+                          html! {
+                            <a class="toolbox-action" href={ FAVORITES_PATH } onclick=self.link.callback(|event: MouseEvent| {
+                              event.prevent_default();
+                              Msg::Navigate(FAVORITES_PATH.to_string())
+                            })>{"Favorites"}</a>
+                          }
+                        }
+                      }
+                      <br/>
+                      <a class="toolbox-action" href="javascript:void(0)" onclick=self.link.callback(|_| Msg::ClearHistory)>{ format!("Clear history ({})", self.history.len()) }</a>
+                      {
+                        if self.broken.is_empty() {
+                          html! { <></> }
+                        } else {
+                          html! { <span class="toolbox-note">{ format!("{} broken", self.broken.len()) }</span> }
+                        }
+                      }
+                      <SettingsPanel prefs=self.prefs.clone()
+                                     invalid=(0..self.prefs.keyword_rules.len()).filter(|index| self.keyword_filter.is_invalid(*index)).collect::<Vec<_>>()
+                                     scope=self.scope()
+                                     overridden=self.scope().is_some_and(|scope| self.settings.overrides.contains_key(&scope))
+                                     on_change=self.link.callback(Msg::PrefsChanged)
+                                     on_override=self.link.callback(Msg::PrefsOverridden)
+                                     on_reset=self.link.callback(|_| Msg::PrefsReset) />
+                      {
+                        if self.prefs.order == PlaybackOrder::Shuffle {
+                          html! {
+                            <>
+                              <strong>{"Shuffle seed"}</strong>
+                              <input type="number" class="seed-input" min="0" value={self.shuffle.seed()} onchange=self.link.callback(Msg::SeedSet) />
+                            </>
+                          }
+                        } else if self.prefs.order == PlaybackOrder::Random && !self.random() {
+                          html! { <span class="toolbox-note">{"Random order needs a subreddit, playing in listing order"}</span> }
+                        } else {
+                          html! { <></> }
+                        }
+                      }
                     </div>
                     <div class="toolbox-body">
                         <ul>
@@ -312,5 +1604,10 @@ impl Component for Model {
 #[wasm_bindgen(start)]
 pub fn run_app() {
   wasm_logger::init(wasm_logger::Config::default());
+  // The same module is loaded by the hash worker, which has no window.
+  if web_sys::window().is_none() {
+    HashWorker::register();
+    return;
+  }
   App::<Model>::new().mount_to_body();
 }
\ No newline at end of file
@@ -0,0 +1,237 @@
+use yew::{Component, ComponentLink, ShouldRender, Html, Properties};
+use yew::prelude::*;
+use yew::format::Json;
+use yew::services::storage::StorageService;
+use anyhow::Error;
+use serde_derive::{Deserialize, Serialize};
+
+const MULTIS_KEY: &str = "MULTIS_KEY";
+/// Local multis play at `MULTI_PREFIX` followed by their name.
+pub(crate) const MULTI_PREFIX: &str = "/m/";
+const SORTS: [&str; 5] = ["hot", "new", "top", "rising", "controversial"];
+const TIMES: [&str; 6] = ["hour", "day", "week", "month", "year", "all"];
+
+/// Named set of subreddits kept in local storage, played like a listing.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct Multireddit {
+  pub(crate) name: String,
+  pub(crate) subreddits: Vec<String>,
+  pub(crate) sort: String,
+  /// Time range for the `top` and `controversial` sorts.
+  #[serde(default)]
+  pub(crate) time: String,
+  /// Take turns between per-subreddit listings instead of reddit's `r/a+b+c`,
+  /// so one busy subreddit can't drown the rest.
+  #[serde(default)]
+  pub(crate) interleave: bool,
+}
+
+/// Route of the multi called `name`.
+pub(crate) fn route(name: &str) -> String {
+  format!("{}{}", MULTI_PREFIX, name)
+}
+
+impl Multireddit {
+  pub(crate) fn route(&self) -> String {
+    route(&self.name)
+  }
+
+  /// Listing query, e.g. the time range of `top`.
+  pub(crate) fn query(&self) -> String {
+    if (self.sort == "top" || self.sort == "controversial") && !self.time.is_empty() {
+      format!("t={}", self.time)
+    } else {
+      String::new()
+    }
+  }
+
+  /// Reddit's own combination of all the subreddits.
+  pub(crate) fn combined_path(&self) -> String {
+    format!("/r/{}/{}", self.subreddits.join("+"), self.sort)
+  }
+
+  pub(crate) fn subreddit_path(&self, subreddit: &str) -> String {
+    format!("/r/{}/{}", subreddit, self.sort)
+  }
+}
+
+pub(crate) fn restore(storage: &StorageService) -> Vec<Multireddit> {
+  let Json(multis): Json<Result<Vec<Multireddit>, Error>> = storage.restore(MULTIS_KEY);
+  multis.unwrap_or_default()
+}
+
+pub(crate) fn store(storage: &mut StorageService, multis: &[Multireddit]) {
+  storage.store(MULTIS_KEY, Json(&multis));
+}
+
+/// Subreddit names out of free text like `earthporn, r/skyporn spaceporn`.
+fn parse_subreddits(value: &str) -> Vec<String> {
+  value.split(|c: char| c == ',' || c == '+' || c.is_whitespace())
+      .map(|name| name.trim_start_matches("/r/").trim_start_matches("r/"))
+      .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+      .map(|name| name.to_string())
+      .collect()
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct MultiEditorProps {
+  pub multis: Vec<Multireddit>,
+  /// Emitted with the name of the multi edited, if any, and the multi to save
+  /// in its place, or in place of the one of the same name.
+  pub on_save: Callback<(Option<String>, Multireddit)>,
+  /// Emitted with the name of the multi to delete.
+  pub on_delete: Callback<String>,
+  /// Emitted with the route of the multi to play.
+  pub on_open: Callback<String>,
+}
+
+pub enum Msg {
+  Toggle,
+  Edit(Multireddit),
+  SetName(InputData),
+  SetSubreddits(InputData),
+  SetSort(ChangeData),
+  SetTime(ChangeData),
+  ToggleInterleave,
+  Save,
+}
+
+/// Lists the local multis and edits them.
+pub struct MultiEditor {
+  props: MultiEditorProps,
+  link: ComponentLink<Self>,
+  open: bool,
+  /// Name of the multi being edited, which saving renames.
+  editing: Option<String>,
+  name: String,
+  subreddits: String,
+  sort: String,
+  time: String,
+  interleave: bool,
+}
+
+impl Component for MultiEditor {
+  type Message = Msg;
+  type Properties = MultiEditorProps;
+
+  fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+    Self {
+      props,
+      link,
+      open: false,
+      editing: None,
+      name: String::new(),
+      subreddits: String::new(),
+      sort: SORTS[0].to_string(),
+      time: TIMES[1].to_string(),
+      interleave: false,
+    }
+  }
+
+  fn update(&mut self, msg: Self::Message) -> ShouldRender {
+    match msg {
+      Msg::Toggle => {
+        self.open = !self.open;
+        self.editing = None;
+      }
+      Msg::Edit(multi) => {
+        self.open = true;
+        self.editing = Some(multi.name.clone());
+        self.name = multi.name;
+        self.subreddits = multi.subreddits.join(" ");
+        self.sort = multi.sort;
+        if !multi.time.is_empty() {
+          self.time = multi.time;
+        }
+        self.interleave = multi.interleave;
+      }
+      Msg::SetName(data) => self.name = data.value,
+      Msg::SetSubreddits(data) => self.subreddits = data.value,
+      Msg::SetSort(data) => {
+        if let ChangeData::Select(select) = data {
+          self.sort = select.value();
+        }
+      }
+      Msg::SetTime(data) => {
+        if let ChangeData::Select(select) = data {
+          self.time = select.value();
+        }
+      }
+      Msg::ToggleInterleave => self.interleave = !self.interleave,
+      Msg::Save => {
+        let name: String = self.name.trim().chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+            .collect();
+        let subreddits = parse_subreddits(&self.subreddits);
+        if name.is_empty() || subreddits.is_empty() {
+          return false;
+        }
+        self.props.on_save.emit((self.editing.take(), Multireddit {
+          name,
+          subreddits,
+          sort: self.sort.clone(),
+          time: self.time.clone(),
+          interleave: self.interleave,
+        }));
+        self.name.clear();
+        self.subreddits.clear();
+      }
+    }
+    true
+  }
+
+  fn change(&mut self, props: Self::Properties) -> ShouldRender {
+    if self.props != props {
+      self.props = props;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn view(&self) -> Html {
+    let view_multi = |multi: &Multireddit| {
+      let route = multi.route();
+      let edited = multi.clone();
+      let deleted = multi.name.clone();
+      html! {
+        <div class="multi">
+          <a class="switcher-link" href=route.clone() title={ multi.subreddits.join(" ") } onclick=self.props.on_open.reform(move |event: MouseEvent| {
+            event.prevent_default();
+            route.clone()
+          })>{ &multi.name }</a>
+          <a class="toolbox-action" href="javascript:void(0)" onclick=self.link.callback(move |_| Msg::Edit(edited.clone()))>{"Edit"}</a>
+          <a class="toolbox-action" href="javascript:void(0)" onclick=self.props.on_delete.reform(move |_| deleted.clone())>{"Delete"}</a>
+        </div>
+      }
+    };
+    html! {
+      <div class="multis">
+        <strong>{"Multis"}</strong>
+        <a class="toolbox-action" href="javascript:void(0)" onclick=self.link.callback(|_| Msg::Toggle)>{ if self.open { "Close" } else { "New" } }</a>
+        { for self.props.multis.iter().map(view_multi) }
+        {
+          if self.open {
+            html! {
+              <div class="multi-editor">
+                <input type="text" placeholder="name" value={ self.name.clone() } oninput=self.link.callback(Msg::SetName) />
+                <input type="text" class="multi-subreddits" placeholder="earthporn skyporn ..." value={ self.subreddits.clone() }
+                       oninput=self.link.callback(Msg::SetSubreddits) /><br/>
+                <select class="toolbox-select" onchange=self.link.callback(Msg::SetSort)>
+                  { for SORTS.iter().map(|sort| html! { <option value={ *sort } selected={ *sort == self.sort }>{ sort }</option> }) }
+                </select>
+                <select class="toolbox-select" onchange=self.link.callback(Msg::SetTime)>
+                  { for TIMES.iter().map(|time| html! { <option value={ *time } selected={ *time == self.time }>{ time }</option> }) }
+                </select>
+                <input type="checkbox" checked={ self.interleave } onchange=self.link.callback(|_| Msg::ToggleInterleave) /> <strong>{"Take turns"}</strong>
+                <a class="toolbox-action" href="javascript:void(0)" onclick=self.link.callback(|_| Msg::Save)>{"Save"}</a>
+              </div>
+            }
+          } else {
+            html! { <></> }
+          }
+        }
+      </div>
+    }
+  }
+}
@@ -1,82 +1,506 @@
 use yew::{Component, ComponentLink, ShouldRender, Html, Properties};
 use yew::prelude::*;
-use web_sys::HtmlVideoElement;
-use wasm_bindgen::JsCast;
+use web_sys::{DomException, HtmlVideoElement};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use log::{info, warn};
+use reddit_data::VideoSource;
+
+// Picture-in-picture is still behind `web_sys_unstable_apis`, so bind the few
+// calls needed here directly.
+#[wasm_bindgen]
+extern "C" {
+  type PipVideo;
+
+  #[wasm_bindgen(method, catch, js_name = requestPictureInPicture)]
+  fn request_picture_in_picture(this: &PipVideo) -> Result<js_sys::Promise, JsValue>;
+
+  type PipDocument;
+
+  #[wasm_bindgen(method, catch, js_name = exitPictureInPicture)]
+  fn exit_picture_in_picture(this: &PipDocument) -> Result<js_sys::Promise, JsValue>;
+
+  #[wasm_bindgen(method, getter, js_name = pictureInPictureEnabled)]
+  fn picture_in_picture_enabled(this: &PipDocument) -> Option<bool>;
+}
+
+const PIP_EVENTS: [&str; 2] = ["enterpictureinpicture", "leavepictureinpicture"];
+
+type PipListener = (HtmlVideoElement, Closure<dyn Fn(Event)>);
+
+const PLAYBACK_RATES: [f64; 7] = [0.25, 0.5, 0.75, 1.0, 1.25, 1.5, 2.0];
+/// The element exposes no frame information, so assume 30 fps.
+const FRAME_STEP: f64 = 1.0 / 30.0;
 
 #[derive(Properties, Clone, PartialEq)]
 pub struct PlayerProps {
-  pub url: String,
-  pub mime: String,
+  /// Candidates tried in order until one loads.
+  pub sources: Vec<VideoSource>,
   pub id: String,
+  #[prop_or(true)]
+  pub muted: bool,
+  #[prop_or(1.0)]
+  pub volume: f64,
+  /// Emitted with `(muted, volume)` when the user changes audio settings,
+  /// once the volume slider is let go.
+  #[prop_or_default]
+  pub on_audio_change: Callback<(bool, f64)>,
+  /// Emitted with `true` while the user is pausing, seeking or using the
+  /// controls, and `false` once they are done.
+  #[prop_or_default]
+  pub on_interaction: Callback<bool>,
+  /// Emitted with `true` when the video enters picture-in-picture and
+  /// `false` when it leaves it.
+  #[prop_or_default]
+  pub on_pip_change: Callback<bool>,
+  /// Only the picture-in-picture window shows the video, hide it here.
+  #[prop_or_default]
+  pub hidden: bool,
+  /// Emitted once the current source has data to show.
+  #[prop_or_default]
+  pub on_load: Callback<()>,
+  /// Emitted with the first source's url once every source failed to load.
+  #[prop_or_default]
+  pub on_error: Callback<String>,
 }
 
 pub enum Msg {
-  Enable,
-  OnLoad(Event),
+  OnLoad,
+  OnError,
+  ToggleMute,
+  SetVolume(InputData),
+  VolumeEnd,
+  AutoplayBlocked,
+  TogglePlay,
+  Played,
+  Paused,
+  TimeUpdate,
+  DurationChange,
+  SeekStart,
+  Seek(InputData),
+  SeekEnd,
+  SetRate(ChangeData),
+  StepFrame(f64),
+  ToggleLoop,
+  ControlsHover(bool),
+  TogglePip,
+  PipChanged(bool),
 }
 
 pub struct Player {
   props: PlayerProps,
-  dirty: bool,
-  callback_enable: Callback<()>,
+  source_index: usize,
+  /// The source changed and the element has to `load()` it after rendering.
+  pending_load: bool,
+  muted: bool,
+  /// Playing muted only because the browser refused to autoplay with sound,
+  /// the stored preference still has sound on.
+  autoplay_muted: bool,
+  volume: f64,
+  rate: f64,
+  looping: bool,
+  pip: bool,
+  playing: bool,
+  user_paused: bool,
+  seeking: bool,
+  hovering: bool,
+  interacting: bool,
+  time: f64,
+  duration: f64,
+  video: NodeRef,
+  /// Element the picture-in-picture listener is attached to; yew has no
+  /// attribute for those events.
+  pip_listener: Option<PipListener>,
   link: ComponentLink<Self>,
 }
 
+fn pip_supported() -> bool {
+  web_sys::window()
+      .and_then(|window| window.document())
+      .and_then(|document| document.unchecked_ref::<PipDocument>().picture_in_picture_enabled())
+      .unwrap_or(false)
+}
+
+fn format_time(seconds: f64) -> String {
+  let seconds = if seconds.is_finite() { seconds.max(0.0) as u64 } else { 0 };
+  format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+impl Player {
+  fn source(&self) -> Option<&VideoSource> {
+    self.props.sources.get(self.source_index)
+  }
+
+  fn element(&self) -> Option<HtmlVideoElement> {
+    self.video.cast::<HtmlVideoElement>()
+  }
+
+  /// Pushes the settings onto the element without re-rendering it. The
+  /// default rate is set too, as `load()` resets the rate to it.
+  fn apply_settings(&self) {
+    if let Some(video) = self.element() {
+      video.set_muted(self.muted);
+      video.set_volume(self.volume);
+      video.set_default_playback_rate(self.rate);
+      video.set_playback_rate(self.rate);
+      video.set_loop(self.looping);
+    }
+  }
+
+  /// Browsers refuse to autoplay with sound until the user interacted with the
+  /// page, so retry muted when that is why playing fails. Other failures, like
+  /// the `AbortError` of a play interrupted by a new source, are left alone.
+  fn play(&self) {
+    if let Some(video) = self.element() {
+      if let Ok(promise) = video.play() {
+        let blocked = self.link.callback(|_| Msg::AutoplayBlocked);
+        spawn_local(async move {
+          if let Err(e) = JsFuture::from(promise).await {
+            if e.dyn_ref::<DomException>().is_some_and(|e| e.name() == "NotAllowedError") {
+              blocked.emit(());
+            }
+          }
+        });
+      }
+    }
+  }
+
+  fn detach_pip_listener(&mut self) {
+    if let Some((video, listener)) = self.pip_listener.take() {
+      for event in PIP_EVENTS.iter() {
+        let _ = video.remove_event_listener_with_callback(event, listener.as_ref().unchecked_ref());
+      }
+    }
+  }
+
+  /// Follows the video element across re-creations (e.g. after an image fallback).
+  fn attach_pip_listener(&mut self) {
+    let video = self.element();
+    if video.as_ref() == self.pip_listener.as_ref().map(|(video, _)| video) {
+      return;
+    }
+    self.detach_pip_listener();
+    if let Some(video) = video {
+      let link = self.link.clone();
+      let listener = Closure::wrap(Box::new(move |event: Event| {
+        link.send_message(Msg::PipChanged(event.type_() == PIP_EVENTS[0]));
+      }) as Box<dyn Fn(Event)>);
+      for event in PIP_EVENTS.iter() {
+        let _ = video.add_event_listener_with_callback(event, listener.as_ref().unchecked_ref());
+      }
+      self.pip_listener = Some((video, listener));
+    }
+  }
+
+  fn update_interaction(&mut self) {
+    let interacting = self.user_paused || self.seeking || self.hovering;
+    if interacting != self.interacting {
+      self.interacting = interacting;
+      self.props.on_interaction.emit(interacting);
+    }
+  }
+}
+
 impl Component for Player {
   type Properties = PlayerProps;
   type Message = Msg;
   fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
     Self {
+      source_index: 0,
+      pending_load: false,
+      muted: props.muted,
+      autoplay_muted: false,
+      volume: props.volume,
+      rate: 1.0,
+      looping: true,
+      pip: false,
+      playing: false,
+      user_paused: false,
+      seeking: false,
+      hovering: false,
+      interacting: false,
+      time: 0.0,
+      duration: 0.0,
       props,
-      dirty: false,
-      callback_enable: link.callback(|_| Msg::Enable),
+      video: NodeRef::default(),
+      pip_listener: None,
       link,
     }
   }
 
   fn update(&mut self, msg: Self::Message) -> ShouldRender {
     match msg {
-      Msg::Enable => {
-        if self.dirty {
-          self.dirty = false;
+      Msg::OnLoad => {
+        self.apply_settings();
+        if !self.muted {
+          self.play();
+        }
+        self.props.on_load.emit(());
+      }
+      Msg::OnError => {
+        self.source_index += 1;
+        if let Some(source) = self.source() {
+          info!("falling back to {}", source.url);
+          self.pending_load = true;
+          return true;
+        }
+        let url = self.props.sources.first().map(|source| source.url.clone()).unwrap_or_default();
+        self.props.on_error.emit(url);
+      }
+      Msg::ToggleMute => {
+        self.muted = !self.muted;
+        self.autoplay_muted = false;
+        self.apply_settings();
+        self.props.on_audio_change.emit((self.muted, self.volume));
+        return true;
+      }
+      Msg::SetVolume(data) => {
+        if let Ok(volume) = data.value.parse::<f64>() {
+          self.volume = volume.clamp(0.0, 1.0);
+          self.muted = self.volume == 0.0;
+          self.autoplay_muted = false;
+          self.apply_settings();
           return true;
         }
       }
-      Msg::OnLoad(event) => {
-        if let Some(target) = event.target() {
-          if let Some(video) = target.dyn_ref::<HtmlVideoElement>() {
-            video.set_muted(true);
+      Msg::VolumeEnd => self.props.on_audio_change.emit((self.muted, self.volume)),
+      Msg::AutoplayBlocked => {
+        if let Some(video) = self.element() {
+          self.muted = true;
+          self.autoplay_muted = true;
+          video.set_muted(true);
+          let _ = video.play();
+          return true;
+        }
+      }
+      Msg::TogglePlay => {
+        if let Some(video) = self.element() {
+          if video.paused() {
+            self.user_paused = false;
+            self.play();
+          } else {
+            self.user_paused = true;
+            let _ = video.pause();
+          }
+          self.update_interaction();
+          return true;
+        }
+      }
+      Msg::Played => {
+        self.playing = true;
+        return true;
+      }
+      Msg::Paused => {
+        self.playing = false;
+        return true;
+      }
+      Msg::TimeUpdate => {
+        if let Some(video) = self.element() {
+          self.time = video.current_time();
+          return !self.seeking;
+        }
+      }
+      Msg::DurationChange => {
+        if let Some(video) = self.element() {
+          self.duration = video.duration();
+          return true;
+        }
+      }
+      Msg::SeekStart => {
+        self.seeking = true;
+        self.update_interaction();
+      }
+      Msg::Seek(data) => {
+        if let (Some(video), Ok(time)) = (self.element(), data.value.parse::<f64>()) {
+          self.time = time;
+          video.set_current_time(time);
+        }
+      }
+      Msg::SeekEnd => {
+        self.seeking = false;
+        self.update_interaction();
+      }
+      Msg::SetRate(data) => {
+        if let ChangeData::Select(select) = data {
+          if let Ok(rate) = select.value().parse::<f64>() {
+            self.rate = rate;
+            self.apply_settings();
+            return true;
+          }
+        }
+      }
+      Msg::StepFrame(direction) => {
+        if let Some(video) = self.element() {
+          if video.paused() {
+            let time = (video.current_time() + direction * FRAME_STEP).max(0.0);
+            video.set_current_time(time);
+            self.time = time;
+            return true;
+          }
+        }
+      }
+      Msg::ToggleLoop => {
+        self.looping = !self.looping;
+        self.apply_settings();
+        return true;
+      }
+      Msg::ControlsHover(hovering) => {
+        self.hovering = hovering;
+        self.update_interaction();
+      }
+      Msg::TogglePip => {
+        let document = web_sys::window().and_then(|window| window.document());
+        if let (Some(video), Some(document)) = (self.element(), document) {
+          let result = if self.pip {
+            document.unchecked_ref::<PipDocument>().exit_picture_in_picture()
+          } else {
+            video.unchecked_ref::<PipVideo>().request_picture_in_picture()
+          };
+          match result {
+            Ok(promise) => spawn_local(async move {
+              if let Err(e) = JsFuture::from(promise).await {
+                warn!("picture-in-picture: {:?}", e);
+              }
+            }),
+            Err(e) => warn!("picture-in-picture: {:?}", e),
           }
         }
       }
+      Msg::PipChanged(pip) => {
+        self.pip = pip;
+        self.props.on_pip_change.emit(pip);
+        return true;
+      }
     }
     false
   }
 
   fn change(&mut self, props: Self::Properties) -> ShouldRender {
-    if self.props != props {
-      self.props = props;
-      if !self.dirty {
-        self.dirty = true;
-        self.callback_enable.emit(());
+    if self.props == props {
+      return false;
+    }
+    let source_changed = self.props.sources != props.sources;
+    if self.props.muted != props.muted || self.props.volume != props.volume {
+      self.muted = props.muted;
+      self.autoplay_muted = false;
+      self.volume = props.volume;
+      self.apply_settings();
+    }
+    self.props = props;
+    if source_changed {
+      self.source_index = 0;
+      self.user_paused = false;
+      self.seeking = false;
+      self.time = 0.0;
+      self.duration = 0.0;
+      self.pending_load = true;
+      self.update_interaction();
+    }
+    true
+  }
+
+  fn rendered(&mut self, _first_render: bool) {
+    if self.pending_load {
+      self.pending_load = false;
+      if let Some(video) = self.element() {
+        video.load();
       }
-      true
-    } else {
-      false
+    }
+    self.apply_settings();
+    self.attach_pip_listener();
+  }
+
+  fn destroy(&mut self) {
+    self.detach_pip_listener();
+    if self.interacting {
+      self.props.on_interaction.emit(false);
+    }
+    if self.pip {
+      self.props.on_pip_change.emit(false);
     }
   }
 
   fn view(&self) -> Html {
-    if !self.dirty {
-      html! {
-         <video id={&self.props.id} autoplay=true loop=true muted=true onloadeddata=self.link.callback(|x| Msg::OnLoad(x))>
-              <source src={ &self.props.url } type={ &self.props.mime } />
-         </video>
-      }
-    } else {
-      html! {
-         <></>
-      }
+    let rate_option = |rate: &f64| {
+      html! { <option value={ rate.to_string() } selected={ *rate == self.rate }>{ format!("{}\u{d7}", rate) }</option> }
+    };
+    let source = match self.source() {
+      Some(source) => source,
+      None => return html! { <></> },
+    };
+    if source.is_image() {
+      return html! {
+        <img id={&self.props.id} class="player-image" src={ &source.url }
+             onload=self.link.callback(|_| Msg::OnLoad) onerror=self.link.callback(|_| Msg::OnError) />
+      };
+    }
+
+    html! {
+      <>
+        <video id={&self.props.id} class={ if self.props.hidden { "player-hidden" } else { "" } } ref=self.video.clone() autoplay=true
+               onloadeddata=self.link.callback(|_| Msg::OnLoad)
+               onplay=self.link.callback(|_| Msg::Played)
+               onpause=self.link.callback(|_| Msg::Paused)
+               ontimeupdate=self.link.callback(|_| Msg::TimeUpdate)
+               ondurationchange=self.link.callback(|_| Msg::DurationChange)>
+          <source src={ &source.url } type={ &source.mime } onerror=self.link.callback(|_| Msg::OnError) />
+        </video>
+        <div class={ if self.props.hidden { "player-controls player-hidden" } else { "player-controls" } }
+             onmouseenter=self.link.callback(|_| Msg::ControlsHover(true))
+             onmouseleave=self.link.callback(|_| Msg::ControlsHover(false))>
+          <a class="player-button" title="Play/pause" onclick=self.link.callback(|_| Msg::TogglePlay)>
+            { if self.playing { "\u{23f8}" } else { "\u{25b6}" } }
+          </a>
+          {
+            if !self.playing {
+              html! {
+                <>
+                  <a class="player-button" title="Previous frame" onclick=self.link.callback(|_| Msg::StepFrame(-1.0))>{ "\u{23ea}" }</a>
+                  <a class="player-button" title="Next frame" onclick=self.link.callback(|_| Msg::StepFrame(1.0))>{ "\u{23e9}" }</a>
+                </>
+              }
+            } else {
+              html! { <></> }
+            }
+          }
+          <input type="range" class="player-seek" min="0" max={ self.duration.max(0.0) } step="0.01" value={ self.time }
+                 onpointerdown=self.link.callback(|_| Msg::SeekStart)
+                 oninput=self.link.callback(Msg::Seek)
+                 onpointerup=self.link.callback(|_| Msg::SeekEnd)
+                 onchange=self.link.callback(|_| Msg::SeekEnd) />
+          <span class="player-time">{ format!("{} / {}", format_time(self.time), format_time(self.duration)) }</span>
+          <select class="player-rate" onchange=self.link.callback(Msg::SetRate)>
+            { for PLAYBACK_RATES.iter().map(rate_option) }
+          </select>
+          <a class={ if self.looping { "player-button player-toggled" } else { "player-button" } } title="Loop"
+             onclick=self.link.callback(|_| Msg::ToggleLoop)>{ "\u{1f501}" }</a>
+          {
+            if self.autoplay_muted {
+              html! { <a class="player-button player-unmute" onclick=self.link.callback(|_| Msg::ToggleMute)>{"Tap to unmute"}</a> }
+            } else {
+              html! {
+                <a class="player-button" title="Mute" onclick=self.link.callback(|_| Msg::ToggleMute)>
+                  { if self.muted { "\u{1f507}" } else { "\u{1f50a}" } }
+                </a>
+              }
+            }
+          }
+          <input type="range" class="player-volume" min="0" max="1" step="0.05"
+                 value={ if self.muted { 0.0 } else { self.volume } } oninput=self.link.callback(Msg::SetVolume)
+                 onchange=self.link.callback(|_| Msg::VolumeEnd) />
+          {
+            if pip_supported() {
+              html! {
+                <a class={ if self.pip { "player-button player-toggled" } else { "player-button" } } title="Picture-in-picture"
+                   onclick=self.link.callback(|_| Msg::TogglePip)>{ "\u{29c9}" }</a>
+              }
+            } else {
+              html! { <></> }
+            }
+          }
+        </div>
+      </>
     }
   }
-}
\ No newline at end of file
+}
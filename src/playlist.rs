@@ -0,0 +1,31 @@
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+
+/// Offers `content` to the user as a downloaded json file.
+pub(crate) fn download_json(file_name: &str, content: &str) -> Result<(), JsValue> {
+  let parts = js_sys::Array::of1(&JsValue::from_str(content));
+  let options = BlobPropertyBag::new();
+  options.set_type("application/json");
+  let blob = Blob::new_with_str_sequence_and_options(&parts, &options)?;
+  let url = Url::create_object_url_with_blob(&blob)?;
+
+  let document = web_sys::window().and_then(|w| w.document()).ok_or_else(|| JsValue::from_str("document not available"))?;
+  let anchor: HtmlAnchorElement = document.create_element("a")?.dyn_into()?;
+  anchor.set_href(&url);
+  anchor.set_download(file_name);
+  anchor.click();
+  Url::revoke_object_url(&url)
+}
+
+/// Turns a listing path like `/r/earthporn/top` into `r_earthporn_top`.
+pub(crate) fn file_stem(path: &str) -> String {
+  let stem = path.trim_matches('/')
+      .chars()
+      .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+      .collect::<String>();
+  if stem.is_empty() {
+    "playlist".to_string()
+  } else {
+    stem
+  }
+}
@@ -0,0 +1,157 @@
+use yew::{Component, ComponentLink, ShouldRender, Html, Properties};
+use yew::prelude::*;
+use reddit_data::{KeywordRule, RuleField};
+
+const FIELDS: [RuleField; 4] = [RuleField::Title, RuleField::Author, RuleField::Flair, RuleField::Domain];
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct RulesPanelProps {
+  pub rules: Vec<KeywordRule>,
+  /// Indices of rules whose regex doesn't compile.
+  pub invalid: Vec<usize>,
+  /// Emitted with the whole new list of rules after every edit.
+  pub on_change: Callback<Vec<KeywordRule>>,
+}
+
+pub enum Msg {
+  Toggle,
+  SetField(ChangeData),
+  SetPattern(InputData),
+  ToggleRegex,
+  SetExclude(ChangeData),
+  Add,
+  Remove(usize),
+}
+
+/// Settings panel for the keyword and flair rules.
+pub struct RulesPanel {
+  props: RulesPanelProps,
+  link: ComponentLink<Self>,
+  open: bool,
+  field: RuleField,
+  pattern: String,
+  regex: bool,
+  exclude: bool,
+}
+
+impl Component for RulesPanel {
+  type Message = Msg;
+  type Properties = RulesPanelProps;
+
+  fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+    Self {
+      props,
+      link,
+      open: false,
+      field: RuleField::Title,
+      pattern: String::new(),
+      regex: false,
+      exclude: true,
+    }
+  }
+
+  fn update(&mut self, msg: Self::Message) -> ShouldRender {
+    match msg {
+      Msg::Toggle => self.open = !self.open,
+      Msg::SetField(data) => {
+        if let ChangeData::Select(select) = data {
+          if let Some(field) = RuleField::parse(&select.value()) {
+            self.field = field;
+          }
+        }
+      }
+      Msg::SetPattern(data) => self.pattern = data.value,
+      Msg::ToggleRegex => self.regex = !self.regex,
+      Msg::SetExclude(data) => {
+        if let ChangeData::Select(select) = data {
+          self.exclude = select.value() == "hide";
+        }
+      }
+      Msg::Add => {
+        if self.pattern.is_empty() {
+          return false;
+        }
+        let mut rules = self.props.rules.clone();
+        rules.push(KeywordRule {
+          field: self.field,
+          pattern: std::mem::take(&mut self.pattern),
+          regex: self.regex,
+          exclude: self.exclude,
+        });
+        self.props.on_change.emit(rules);
+      }
+      Msg::Remove(index) => {
+        let mut rules = self.props.rules.clone();
+        if index < rules.len() {
+          rules.remove(index);
+          self.props.on_change.emit(rules);
+        }
+        return false;
+      }
+    }
+    true
+  }
+
+  fn change(&mut self, props: Self::Properties) -> ShouldRender {
+    if self.props != props {
+      self.props = props;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn view(&self) -> Html {
+    let view_rule = |(index, rule): (usize, &KeywordRule)| {
+      html! {
+        <li>
+          { if rule.exclude { "hide " } else { "only " } }
+          { rule.field.as_str() }
+          { if rule.regex { " matching " } else { " containing " } }
+          <code>{ &rule.pattern }</code>
+          {
+            if self.props.invalid.contains(&index) {
+              html! { <span class="toolbox-note">{"invalid regex"}</span> }
+            } else {
+              html! { <></> }
+            }
+          }
+          <a class="toolbox-action" href="javascript:void(0)" onclick=self.link.callback(move |_| Msg::Remove(index))>{"Remove"}</a>
+        </li>
+      }
+    };
+    html! {
+      <div class="rules">
+        <strong>{"Rules"}</strong>
+        <a class="toolbox-action" href="javascript:void(0)" onclick=self.link.callback(|_| Msg::Toggle)>
+          { if self.open { "Close".to_string() } else { format!("Edit ({})", self.props.rules.len()) } }
+        </a>
+        {
+          if self.open {
+            html! {
+              <>
+                <ul class="rules-list">
+                  { for self.props.rules.iter().enumerate().map(view_rule) }
+                </ul>
+                <select class="toolbox-select" onchange=self.link.callback(Msg::SetExclude)>
+                  <option value="hide" selected={ self.exclude }>{"hide"}</option>
+                  <option value="only" selected={ !self.exclude }>{"only"}</option>
+                </select>
+                <select class="toolbox-select" onchange=self.link.callback(Msg::SetField)>
+                  { for FIELDS.iter().map(|field| html! {
+                    <option value={ field.as_str() } selected={ *field == self.field }>{ field.as_str() }</option>
+                  }) }
+                </select>
+                <input type="text" class="rule-pattern" placeholder="[OC]" value={ self.pattern.clone() } oninput=self.link.callback(Msg::SetPattern) />
+                <input type="checkbox" checked={ self.regex } onchange=self.link.callback(|_| Msg::ToggleRegex) /> <strong>{"Regex"}</strong>
+                <a class="toolbox-action" href="javascript:void(0)" onclick=self.link.callback(|_| Msg::Add)>{"Add"}</a>
+              </>
+            }
+          } else {
+            html! { <></> }
+          }
+        }
+      </div>
+    }
+  }
+}
@@ -0,0 +1,498 @@
+use std::collections::BTreeMap;
+use yew::{Component, ComponentLink, ShouldRender, Html, Properties};
+use yew::prelude::*;
+use yew::format::Json;
+use yew::services::storage::StorageService;
+use anyhow::Error;
+use log::{info, warn};
+use serde_derive::{Deserialize, Serialize};
+use reddit_data::{parse_domains, ContentFilter, KeywordRule, Orientation, PostThresholds};
+
+use crate::rules::RulesPanel;
+
+const SETTINGS_KEY: &str = "SETTINGS_KEY";
+/// Bump when `Settings` changes in a way `#[serde(default)]` can't absorb,
+/// and migrate in `Settings::restore`.
+pub(crate) const SETTINGS_VERSION: u32 = 1;
+
+// Keys the slideshow timeout used to be stored under, read once to migrate.
+const TIMEOUT_KEY: &str = "TIMEOUT_KEY";
+const TIMEOUT_ENABLED_KEY: &str = "TIMEOUT_ENABLED_KEY";
+const LEGACY_KEYS: [&str; 2] = [TIMEOUT_KEY, TIMEOUT_ENABLED_KEY];
+
+/// What to do with pictures that look like something already seen.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum RepostMode {
+  Off,
+  Flag,
+  Hide,
+}
+
+impl RepostMode {
+  pub(crate) fn as_str(self) -> &'static str {
+    match self {
+      RepostMode::Off => "off",
+      RepostMode::Flag => "flag",
+      RepostMode::Hide => "hide",
+    }
+  }
+
+  pub(crate) fn parse(value: &str) -> Option<Self> {
+    match value {
+      "off" => Some(RepostMode::Off),
+      "flag" => Some(RepostMode::Flag),
+      "hide" => Some(RepostMode::Hide),
+      _ => None,
+    }
+  }
+}
+
+/// How one item gives way to the next.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Transition {
+  #[serde(rename = "none")]
+  Cut,
+  Crossfade,
+  Slide,
+}
+
+impl Transition {
+  pub(crate) fn as_str(self) -> &'static str {
+    match self {
+      Transition::Cut => "none",
+      Transition::Crossfade => "crossfade",
+      Transition::Slide => "slide",
+    }
+  }
+
+  pub(crate) fn parse(value: &str) -> Option<Self> {
+    match value {
+      "none" => Some(Transition::Cut),
+      "crossfade" => Some(Transition::Crossfade),
+      "slide" => Some(Transition::Slide),
+      _ => None,
+    }
+  }
+}
+
+/// Order in which the slideshow walks through items.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PlaybackOrder {
+  Sequential,
+  /// Loaded items in a reproducible random order, see `Model::shuffle`.
+  Shuffle,
+  /// One post at a time from the listing's `/random` endpoint.
+  Random,
+}
+
+impl PlaybackOrder {
+  pub(crate) fn as_str(self) -> &'static str {
+    match self {
+      PlaybackOrder::Sequential => "sequential",
+      PlaybackOrder::Shuffle => "shuffle",
+      PlaybackOrder::Random => "random",
+    }
+  }
+
+  pub(crate) fn parse(value: &str) -> Option<Self> {
+    match value {
+      "sequential" => Some(PlaybackOrder::Sequential),
+      "shuffle" => Some(PlaybackOrder::Shuffle),
+      "random" => Some(PlaybackOrder::Random),
+      _ => None,
+    }
+  }
+}
+
+/// Everything the user can set, as a whole or for one subreddit. Missing
+/// fields take their default, so adding one needs no migration.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub(crate) struct Preferences {
+  /// Auto next interval in seconds.
+  pub(crate) timeout: u64,
+  pub(crate) timeout_enabled: bool,
+  pub(crate) skip_seen: bool,
+  pub(crate) dedup_crossposts: bool,
+  pub(crate) repost_mode: RepostMode,
+  /// Largest perceptual hash distance, in bits, still counted as a repost.
+  pub(crate) repost_distance: u32,
+  pub(crate) sound_on: bool,
+  pub(crate) volume: f64,
+  pub(crate) transition: Transition,
+  pub(crate) ken_burns: bool,
+  pub(crate) order: PlaybackOrder,
+  /// Push a history entry per item rather than only per route.
+  pub(crate) item_history: bool,
+  pub(crate) content_filter: ContentFilter,
+  pub(crate) keyword_rules: Vec<KeywordRule>,
+  /// Score and age limits, applied to pages loaded after they change.
+  pub(crate) thresholds: PostThresholds,
+}
+
+impl Default for Preferences {
+  fn default() -> Self {
+    Self {
+      timeout: 10,
+      timeout_enabled: true,
+      skip_seen: false,
+      dedup_crossposts: false,
+      repost_mode: RepostMode::Off,
+      repost_distance: 6,
+      sound_on: false,
+      volume: 1.0,
+      transition: Transition::Cut,
+      ken_burns: false,
+      order: PlaybackOrder::Sequential,
+      item_history: false,
+      content_filter: ContentFilter::default(),
+      keyword_rules: Vec::new(),
+      thresholds: PostThresholds::default(),
+    }
+  }
+}
+
+/// Restores a value stored as its `to_string()` under one of the legacy keys.
+fn restore_legacy<T: std::str::FromStr>(storage: &StorageService, key: &str, value: &mut T) {
+  if let Ok(stored) = storage.restore::<Result<String, Error>>(key) {
+    if let Ok(stored) = stored.parse::<T>() {
+      *value = stored;
+    }
+  }
+}
+
+impl Preferences {
+  /// Preferences as they were kept before `Settings`: just the timeout.
+  fn from_legacy(storage: &StorageService) -> Self {
+    let mut prefs = Self::default();
+    restore_legacy(storage, TIMEOUT_KEY, &mut prefs.timeout);
+    restore_legacy(storage, TIMEOUT_ENABLED_KEY, &mut prefs.timeout_enabled);
+    prefs
+  }
+}
+
+/// All preferences, stored as one json blob under `SETTINGS_KEY`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct Settings {
+  pub(crate) version: u32,
+  pub(crate) base: Preferences,
+  /// Full sets of preferences replacing `base` on some routes, by `scope_of`.
+  #[serde(default)]
+  pub(crate) overrides: BTreeMap<String, Preferences>,
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Self {
+      version: SETTINGS_VERSION,
+      base: Preferences::default(),
+      overrides: BTreeMap::new(),
+    }
+  }
+}
+
+/// Key of the overrides for a route: `r/earthporn` for `/r/earthporn/top`,
+/// `m/name` for a local multi.
+pub(crate) fn scope_of(route: &str) -> Option<String> {
+  let mut parts = route.trim_start_matches('/').split('/');
+  match (parts.next(), parts.next()) {
+    (Some(kind @ ("r" | "m")), Some(name)) if !name.is_empty() => Some(format!("{}/{}", kind, name.to_ascii_lowercase())),
+    _ => None,
+  }
+}
+
+impl Settings {
+  /// Restores the stored settings, migrating the legacy keys when there are
+  /// none yet. Settings that don't parse, e.g. written by a newer version in
+  /// another tab, are left in storage and defaults are used meanwhile.
+  pub(crate) fn restore(storage: &mut StorageService) -> Self {
+    let stored: Result<String, Error> = storage.restore(SETTINGS_KEY);
+    match stored.map(|json| serde_json::from_str::<Settings>(&json)) {
+      Ok(Ok(settings)) => {
+        if settings.version > SETTINGS_VERSION {
+          warn!("settings version {} is newer than {}", settings.version, SETTINGS_VERSION);
+        }
+        settings
+      }
+      Ok(Err(e)) => {
+        warn!("keeping unreadable settings, using defaults: {}", e);
+        Settings::default()
+      }
+      Err(_) => {
+        let settings = Settings { base: Preferences::from_legacy(storage), ..Settings::default() };
+        info!("migrated settings to version {}", SETTINGS_VERSION);
+        settings.store(storage);
+        for key in LEGACY_KEYS.iter() {
+          storage.remove(key);
+        }
+        settings
+      }
+    }
+  }
+
+  pub(crate) fn store(&self, storage: &mut StorageService) {
+    storage.store(SETTINGS_KEY, Json(self));
+  }
+
+  /// Preferences in effect on a route.
+  pub(crate) fn for_scope(&self, scope: Option<&String>) -> &Preferences {
+    scope.and_then(|scope| self.overrides.get(scope)).unwrap_or(&self.base)
+  }
+
+  /// Scope whose preferences an edit on a route changes: the route's own if
+  /// it has an override, `None` for the base preferences otherwise.
+  pub(crate) fn edited_scope(&self, scope: Option<String>) -> Option<String> {
+    scope.filter(|scope| self.overrides.contains_key(scope))
+  }
+
+  /// Replaces the preferences of one scope, `None` for the base ones. An
+  /// override set to `None` is removed, the base is reset to defaults.
+  pub(crate) fn set(&mut self, scope: Option<String>, prefs: Option<Preferences>) {
+    match (scope, prefs) {
+      (Some(scope), Some(prefs)) => {
+        self.overrides.insert(scope, prefs);
+      }
+      (Some(scope), None) => {
+        self.overrides.remove(&scope);
+      }
+      (None, prefs) => self.base = prefs.unwrap_or_default(),
+    }
+  }
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct SettingsPanelProps {
+  /// Preferences in effect on the current route.
+  pub prefs: Preferences,
+  /// Indices of keyword rules whose regex doesn't compile.
+  pub invalid: Vec<usize>,
+  /// Scope of the current route, when it can have its own preferences.
+  pub scope: Option<String>,
+  /// Whether the current route uses its own preferences.
+  pub overridden: bool,
+  /// Emitted with the whole new preferences after every edit.
+  pub on_change: Callback<Preferences>,
+  /// Emitted with whether the current route should have its own preferences.
+  pub on_override: Callback<bool>,
+  pub on_reset: Callback<()>,
+}
+
+pub enum Msg {
+  Toggle,
+  Change(Preferences),
+}
+
+/// Panel editing every preference.
+pub struct SettingsPanel {
+  props: SettingsPanelProps,
+  link: ComponentLink<Self>,
+  open: bool,
+}
+
+impl SettingsPanel {
+  /// Callback applying `edit` with the event to a copy of the preferences.
+  fn edit<E: 'static>(&self, edit: impl Fn(&mut Preferences, E) + 'static) -> Callback<E> {
+    let prefs = self.props.prefs.clone();
+    self.link.callback(move |event: E| {
+      let mut prefs = prefs.clone();
+      edit(&mut prefs, event);
+      Msg::Change(prefs)
+    })
+  }
+
+  fn view_toggle(&self, label: &str, checked: bool, toggle: fn(&mut Preferences)) -> Html {
+    html! {
+      <>
+        <input type="checkbox" checked={ checked } onchange=self.edit(move |prefs, _: ChangeData| toggle(prefs)) /> <strong>{ label }</strong>
+      </>
+    }
+  }
+
+  /// Number input, `set` getting `None` when it is emptied.
+  fn view_number(&self, label: &str, class: &str, value: String, step: &str, set: fn(&mut Preferences, Option<f64>)) -> Html {
+    html! {
+      <>
+        { label }
+        <input type="number" class={ class } min="0" step={ step } value={ value }
+               onchange=self.edit(move |prefs, data: ChangeData| {
+                 if let ChangeData::Value(value) = data {
+                   set(prefs, value.parse::<f64>().ok().filter(|value| *value >= 0.0));
+                 }
+               }) />
+      </>
+    }
+  }
+
+  fn view_domains(&self, placeholder: &str, domains: &[String], set: fn(&mut ContentFilter, Vec<String>)) -> Html {
+    html! {
+      <input type="text" class="domains-input" placeholder={ placeholder } value={ domains.join(" ") }
+             onchange=self.edit(move |prefs, data: ChangeData| {
+               if let ChangeData::Value(value) = data {
+                 set(&mut prefs.content_filter, parse_domains(&value));
+               }
+             }) />
+    }
+  }
+
+  fn view_select<T: Copy + PartialEq + 'static>(&self, options: &[T], selected: T, as_str: fn(T) -> &'static str,
+                                                 parse: fn(&str) -> Option<T>, set: fn(&mut Preferences, T)) -> Html {
+    html! {
+      <select class="toolbox-select" onchange=self.edit(move |prefs, data: ChangeData| {
+        if let ChangeData::Select(select) = data {
+          if let Some(value) = parse(&select.value()) {
+            set(prefs, value);
+          }
+        }
+      })>
+        { for options.iter().map(|option| html! {
+          <option value={ as_str(*option) } selected={ *option == selected }>{ as_str(*option) }</option>
+        }) }
+      </select>
+    }
+  }
+
+  fn view_body(&self) -> Html {
+    let prefs = &self.props.prefs;
+    let filter = &prefs.content_filter;
+    let thresholds = &prefs.thresholds;
+    html! {
+      <div class="settings-body">
+        <input type="checkbox" checked={ prefs.timeout_enabled } onchange=self.edit(|prefs, _: ChangeData| prefs.timeout_enabled = !prefs.timeout_enabled) />
+        <strong>{"Auto next"}</strong>{" every"}
+        <input type="number" class="number-input" value={ prefs.timeout } onchange=self.edit(|prefs, data: ChangeData| {
+          if let ChangeData::Value(value) = data {
+            if let Ok(timeout) = value.parse::<u64>() {
+              if timeout > 0 {
+                prefs.timeout = timeout;
+              }
+            }
+          }
+        }) /> {"seconds"}<br/>
+        { self.view_toggle("Skip seen", prefs.skip_seen, |prefs| prefs.skip_seen = !prefs.skip_seen) }
+        { self.view_toggle("Back and forward per item", prefs.item_history, |prefs| prefs.item_history = !prefs.item_history) }<br/>
+        { self.view_toggle("Hide crossposts of loaded posts", prefs.dedup_crossposts, |prefs| prefs.dedup_crossposts = !prefs.dedup_crossposts) }<br/>
+        <strong>{"Reposts"}</strong>
+        { self.view_select(&[RepostMode::Off, RepostMode::Flag, RepostMode::Hide], prefs.repost_mode,
+                           RepostMode::as_str, RepostMode::parse, |prefs, mode| prefs.repost_mode = mode) }
+        {" within"}
+        <input type="number" class="number-input" min="0" max="64" value={ prefs.repost_distance } onchange=self.edit(|prefs, data: ChangeData| {
+          if let ChangeData::Value(value) = data {
+            if let Ok(distance) = value.parse::<u32>() {
+              prefs.repost_distance = distance;
+            }
+          }
+        }) /> {"bits"}<br/>
+        { self.view_toggle("Pictures", filter.pictures, |prefs| prefs.content_filter.pictures = !prefs.content_filter.pictures) }
+        { self.view_toggle("Videos", filter.videos, |prefs| prefs.content_filter.videos = !prefs.content_filter.videos) }
+        { self.view_toggle("Embeds", filter.embeds, |prefs| prefs.content_filter.embeds = !prefs.content_filter.embeds) }
+        { self.view_toggle("Galleries", filter.galleries, |prefs| prefs.content_filter.galleries = !prefs.content_filter.galleries) }<br/>
+        { self.view_domains("only domains", &filter.allow_domains, |filter, domains| filter.allow_domains = domains) }
+        { self.view_domains("never domains", &filter.deny_domains, |filter, domains| filter.deny_domains = domains) }<br/>
+        <strong>{"At least"}</strong>
+        { self.view_number(" w", "size-input", filter.min_width.to_string(), "1",
+                           |prefs, value| prefs.content_filter.min_width = value.unwrap_or(0.0) as i32) }
+        { self.view_number(" h", "size-input", filter.min_height.to_string(), "1",
+                           |prefs, value| prefs.content_filter.min_height = value.unwrap_or(0.0) as i32) }
+        { self.view_number(" MP", "size-input", filter.min_megapixels.to_string(), "0.1",
+                           |prefs, value| prefs.content_filter.min_megapixels = value.unwrap_or(0.0)) }<br/>
+        { self.view_select(&[Orientation::Any, Orientation::Landscape, Orientation::Portrait, Orientation::Square], filter.orientation,
+                           Orientation::as_str, Orientation::parse, |prefs, orientation| prefs.content_filter.orientation = orientation) }
+        { self.view_toggle("Match screen shape", filter.match_screen, |prefs| prefs.content_filter.match_screen = !prefs.content_filter.match_screen) }<br/>
+        <strong>{"Posts"}</strong>
+        { self.view_number(" score \u{2265}", "size-input", thresholds.min_score.map(|min| min.to_string()).unwrap_or_default(), "1",
+                           |prefs, value| prefs.thresholds.min_score = value.map(|value| value as i64)) }
+        { self.view_number(" comments \u{2265}", "size-input", thresholds.min_comments.map(|min| min.to_string()).unwrap_or_default(), "1",
+                           |prefs, value| prefs.thresholds.min_comments = value.map(|value| value as i64)) }<br/>
+        { self.view_number("age \u{2265}", "size-input", thresholds.min_age_hours.map(|min| min.to_string()).unwrap_or_default(), "1",
+                           |prefs, value| prefs.thresholds.min_age_hours = value) }
+        { self.view_number(" \u{2264}", "size-input", thresholds.max_age_hours.map(|max| max.to_string()).unwrap_or_default(), "1",
+                           |prefs, value| prefs.thresholds.max_age_hours = value) }{" hours"}<br/>
+        <RulesPanel rules=prefs.keyword_rules.clone()
+                    invalid=self.props.invalid.clone()
+                    on_change=self.edit(|prefs, rules| prefs.keyword_rules = rules) />
+        <strong>{"Transition"}</strong>
+        { self.view_select(&[Transition::Cut, Transition::Crossfade, Transition::Slide], prefs.transition,
+                           Transition::as_str, Transition::parse, |prefs, transition| prefs.transition = transition) }
+        { self.view_toggle("Pan and zoom pictures", prefs.ken_burns, |prefs| prefs.ken_burns = !prefs.ken_burns) }<br/>
+        <strong>{"Order"}</strong>
+        { self.view_select(&[PlaybackOrder::Sequential, PlaybackOrder::Shuffle, PlaybackOrder::Random], prefs.order,
+                           PlaybackOrder::as_str, PlaybackOrder::parse, |prefs, order| prefs.order = order) }
+      </div>
+    }
+  }
+}
+
+impl Component for SettingsPanel {
+  type Message = Msg;
+  type Properties = SettingsPanelProps;
+
+  fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+    Self {
+      props,
+      link,
+      open: false,
+    }
+  }
+
+  fn update(&mut self, msg: Self::Message) -> ShouldRender {
+    match msg {
+      Msg::Toggle => {
+        self.open = !self.open;
+        true
+      }
+      Msg::Change(prefs) => {
+        self.props.on_change.emit(prefs);
+        false
+      }
+    }
+  }
+
+  fn change(&mut self, props: Self::Properties) -> ShouldRender {
+    if self.props != props {
+      self.props = props;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn view(&self) -> Html {
+    html! {
+      <div class="settings">
+        <strong>{"Settings"}</strong>
+        <a class="toolbox-action" href="javascript:void(0)" onclick=self.link.callback(|_| Msg::Toggle)>
+          { if self.open { "Close" } else { "Edit" } }
+        </a>
+        {
+          match &self.props.scope {
+            Some(scope) if self.open => {
+              let overridden = self.props.overridden;
+              html! {
+                <>
+                  <input type="checkbox" checked={ overridden } onchange=self.props.on_override.reform(move |_| !overridden) />
+                  <strong>{ format!("Only for {}", scope) }</strong>
+                </>
+              }
+            }
+            _ => html! { <></> },
+          }
+        }
+        {
+          if self.open {
+            html! {
+              <>
+                <a class="toolbox-action" href="javascript:void(0)" onclick=self.props.on_reset.reform(|_| ())>{"Reset to defaults"}</a>
+                { self.view_body() }
+              </>
+            }
+          } else {
+            html! { <></> }
+          }
+        }
+      </div>
+    }
+  }
+}
@@ -0,0 +1,266 @@
+use yew::{Component, ComponentLink, ShouldRender, Html, Properties};
+use yew::prelude::*;
+use yew::format::{Json, Nothing};
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
+use yew::services::storage::{Area, StorageService};
+use yew::services::timeout::TimeoutTask;
+use yew::services::TimeoutService;
+use anyhow::Error;
+use log::error;
+use reddit_data::SubredditAutocomplete;
+use std::time::Duration;
+
+const RECENT_SUBREDDITS_KEY: &str = "RECENT_SUBREDDITS_KEY";
+const PINNED_SUBREDDITS_KEY: &str = "PINNED_SUBREDDITS_KEY";
+const MAX_RECENT: usize = 10;
+/// Wait for typing to settle before asking for suggestions.
+const AUTOCOMPLETE_DELAY: Duration = Duration::from_millis(250);
+
+/// Subreddit name of a route like `/r/earthporn/top`.
+fn subreddit_of(route: &str) -> Option<&str> {
+  route.strip_prefix("/r/")
+      .and_then(|rest| rest.split(['/', '?']).next())
+      .filter(|name| !name.is_empty())
+}
+
+/// Keeps what reddit allows in a subreddit name, so it can go in a url as is.
+fn clean_name(value: &str) -> String {
+  value.trim().trim_start_matches("/r/").trim_start_matches("r/")
+      .chars()
+      .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+      .collect()
+}
+
+#[derive(Properties, Clone, PartialEq)]
+pub struct SwitcherProps {
+  /// Route currently playing, recorded as visited when it is a subreddit.
+  pub route: String,
+  /// Emitted with the route of the subreddit picked.
+  pub on_switch: Callback<String>,
+}
+
+pub enum Msg {
+  Input(InputData),
+  KeyDown(KeyboardEvent),
+  Autocomplete,
+  Suggestions(Vec<String>),
+  Switch(String),
+  TogglePin(String),
+}
+
+/// Subreddit input with suggestions and lists of recent and pinned subreddits.
+pub struct Switcher {
+  props: SwitcherProps,
+  link: ComponentLink<Self>,
+  storage: Option<StorageService>,
+  value: String,
+  suggestions: Vec<String>,
+  recent: Vec<String>,
+  pinned: Vec<String>,
+  delay_job: Option<TimeoutTask>,
+  ft: Option<FetchTask>,
+}
+
+impl Switcher {
+  #[cfg(not(feature = "backend"))]
+  fn autocomplete_url(name: &str) -> String {
+    format!("https://www.reddit.com/api/subreddit_autocomplete.json?query={}&include_over_18=true&include_profiles=false", name)
+  }
+
+  #[cfg(feature = "backend")]
+  fn autocomplete_url(name: &str) -> String {
+    format!("/api/subreddit_autocomplete?query={}&include_over_18=true&include_profiles=false", name)
+  }
+
+  fn fetch_suggestions(&mut self) {
+    let name = clean_name(&self.value);
+    if name.is_empty() {
+      return;
+    }
+    let callback = self.link.callback(
+      move |response: Response<Json<Result<SubredditAutocomplete, Error>>>| {
+        let (meta, Json(data)) = response.into_parts();
+        match data {
+          Ok(data) if meta.status.is_success() => {
+            Msg::Suggestions(data.subreddits.into_iter().map(|subreddit| subreddit.name).collect())
+          }
+          Ok(_) => Msg::Suggestions(Vec::new()),
+          Err(e) => {
+            error!("{}", e);
+            Msg::Suggestions(Vec::new())
+          }
+        }
+      },
+    );
+    let request = Request::get(Self::autocomplete_url(&name)).body(Nothing).unwrap();
+    match FetchService::fetch(request, callback) {
+      Ok(task) => self.ft = Some(task),
+      Err(e) => error!("{}", e),
+    }
+  }
+
+  /// Moves the playing subreddit to the front of the recent list.
+  fn record_visit(&mut self) {
+    let name = match subreddit_of(&self.props.route) {
+      Some(name) => name.to_string(),
+      None => return,
+    };
+    self.recent.retain(|recent| !recent.eq_ignore_ascii_case(&name));
+    self.recent.insert(0, name);
+    self.recent.truncate(MAX_RECENT);
+    if let Some(storage) = &mut self.storage {
+      storage.store(RECENT_SUBREDDITS_KEY, Json(&self.recent));
+    }
+  }
+
+  fn view_link(&self, name: &str) -> Html {
+    let route = format!("/r/{}", name);
+    html! {
+      <a class="switcher-link" href=route.clone() onclick=self.link.callback(move |event: MouseEvent| {
+        event.prevent_default();
+        Msg::Switch(route.clone())
+      })>{ name }</a>
+    }
+  }
+}
+
+impl Component for Switcher {
+  type Message = Msg;
+  type Properties = SwitcherProps;
+
+  fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+    let storage = StorageService::new(Area::Local).ok();
+    let mut recent = Vec::new();
+    let mut pinned = Vec::new();
+    if let Some(storage) = &storage {
+      let Json(recent_val): Json<Result<Vec<String>, Error>> = storage.restore(RECENT_SUBREDDITS_KEY);
+      if let Ok(recent_val) = recent_val {
+        recent = recent_val;
+      }
+      let Json(pinned_val): Json<Result<Vec<String>, Error>> = storage.restore(PINNED_SUBREDDITS_KEY);
+      if let Ok(pinned_val) = pinned_val {
+        pinned = pinned_val;
+      }
+    }
+    let mut switcher = Self {
+      props,
+      link,
+      storage,
+      value: String::new(),
+      suggestions: Vec::new(),
+      recent,
+      pinned,
+      delay_job: None,
+      ft: None,
+    };
+    switcher.record_visit();
+    switcher
+  }
+
+  fn update(&mut self, msg: Self::Message) -> ShouldRender {
+    match msg {
+      Msg::Input(data) => {
+        self.value = data.value;
+        self.ft.take();
+        self.suggestions.clear();
+        self.delay_job = Some(TimeoutService::spawn(AUTOCOMPLETE_DELAY, self.link.callback(|_| Msg::Autocomplete)));
+      }
+      Msg::KeyDown(event) => {
+        if event.key() != "Enter" {
+          return false;
+        }
+        let name = clean_name(&self.value);
+        if name.is_empty() {
+          return false;
+        }
+        self.link.send_message(Msg::Switch(format!("/r/{}", name)));
+        return false;
+      }
+      Msg::Autocomplete => {
+        self.delay_job.take();
+        self.fetch_suggestions();
+        return false;
+      }
+      Msg::Suggestions(suggestions) => {
+        self.ft.take();
+        self.suggestions = suggestions;
+      }
+      Msg::Switch(route) => {
+        self.value.clear();
+        self.suggestions.clear();
+        self.delay_job.take();
+        self.ft.take();
+        self.props.on_switch.emit(route);
+      }
+      Msg::TogglePin(name) => {
+        if self.pinned.iter().any(|pinned| pinned.eq_ignore_ascii_case(&name)) {
+          self.pinned.retain(|pinned| !pinned.eq_ignore_ascii_case(&name));
+        } else {
+          self.pinned.push(name);
+        }
+        if let Some(storage) = &mut self.storage {
+          storage.store(PINNED_SUBREDDITS_KEY, Json(&self.pinned));
+        }
+      }
+    }
+    true
+  }
+
+  fn change(&mut self, props: Self::Properties) -> ShouldRender {
+    if self.props != props {
+      let visited = self.props.route != props.route;
+      self.props = props;
+      if visited {
+        self.record_visit();
+      }
+      true
+    } else {
+      false
+    }
+  }
+
+  fn view(&self) -> Html {
+    let current = subreddit_of(&self.props.route).map(|name| name.to_string());
+    let pinned = current.as_ref().is_some_and(|name| self.pinned.iter().any(|pinned| pinned.eq_ignore_ascii_case(name)));
+    html! {
+      <div class="switcher">
+        <input type="text" class="switcher-input" placeholder={ self.props.route.clone() } value={ self.value.clone() }
+               oninput=self.link.callback(Msg::Input) onkeydown=self.link.callback(Msg::KeyDown) />
+        {
+          match current {
+            Some(name) => html! {
+              <a class={ if pinned { "favorite-toggle favorite" } else { "favorite-toggle" } } href="javascript:void(0)"
+                 title="Pin" onclick=self.link.callback(move |_| Msg::TogglePin(name.clone()))>{ "\u{2605}" }</a>
+            },
+            None => html! { <></> },
+          }
+        }
+        {
+          if self.suggestions.is_empty() {
+            html! { <></> }
+          } else {
+            html! {
+              <ul class="switcher-suggestions">
+                { for self.suggestions.iter().map(|name| html! { <li>{ self.view_link(name) }</li> }) }
+              </ul>
+            }
+          }
+        }
+        {
+          if self.pinned.is_empty() {
+            html! { <></> }
+          } else {
+            html! { <div class="switcher-list">{"Pinned "}{ for self.pinned.iter().map(|name| self.view_link(name)) }</div> }
+          }
+        }
+        {
+          if self.recent.is_empty() {
+            html! { <></> }
+          } else {
+            html! { <div class="switcher-list">{"Recent "}{ for self.recent.iter().map(|name| self.view_link(name)) }</div> }
+          }
+        }
+      </div>
+    }
+  }
+}
@@ -0,0 +1,63 @@
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{BroadcastChannel, MessageEvent};
+use yew::Callback;
+use log::error;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::favorites::Favorite;
+use crate::settings::Preferences;
+
+const CHANNEL: &str = "reddit-gallery";
+
+/// Change made in one tab, replayed by the others. Set-like data travels as
+/// additions and removals so tabs editing at the same time merge rather than
+/// overwrite each other.
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) enum SyncMessage {
+  /// Preferences of one scope, see `Settings::set`.
+  Prefs { scope: Option<String>, prefs: Option<Preferences> },
+  Seen { names: Vec<String>, hashes: Vec<u64> },
+  HistoryCleared,
+  /// Favourites added or moved.
+  FavoritesPut(Vec<Favorite>),
+  /// Media url of the favourite removed.
+  FavoriteRemoved(String),
+}
+
+/// Other tabs of the app on the same origin, reached over a `BroadcastChannel`.
+pub(crate) struct TabSync {
+  channel: BroadcastChannel,
+  _listener: Closure<dyn Fn(MessageEvent)>,
+}
+
+impl TabSync {
+  pub(crate) fn open(callback: Callback<SyncMessage>) -> Result<Self, JsValue> {
+    let channel = BroadcastChannel::new(CHANNEL)?;
+    let listener = Closure::wrap(Box::new(move |event: MessageEvent| {
+      match event.data().as_string().map(|json| serde_json::from_str::<SyncMessage>(&json)) {
+        Some(Ok(message)) => callback.emit(message),
+        Some(Err(e)) => error!("skipping sync message: {}", e),
+        None => error!("skipping sync message: not a string"),
+      }
+    }) as Box<dyn Fn(MessageEvent)>);
+    channel.set_onmessage(Some(listener.as_ref().unchecked_ref()));
+    Ok(Self { channel, _listener: listener })
+  }
+
+  /// Hands `message` to every other tab, this one doesn't receive it.
+  pub(crate) fn send(&self, message: &SyncMessage) {
+    let result = serde_json::to_string(message)
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+        .and_then(|json| self.channel.post_message(&JsValue::from_str(&json)));
+    if let Err(e) = result {
+      error!("{:?}", e);
+    }
+  }
+}
+
+impl Drop for TabSync {
+  fn drop(&mut self) {
+    self.channel.close();
+  }
+}